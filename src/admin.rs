@@ -0,0 +1,337 @@
+use crate::config::constant_time_eq;
+use crate::crawler;
+use crate::export;
+use crate::metrics;
+use crate::proxy_pool::{parse_proxy_line, percent_decode, ProxyEntry, ProxyPool};
+use crate::socks_server::SocksServer;
+use anyhow::Result;
+use colored::*;
+use hyper::body::HttpBody;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+// 请求体的最大长度
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+// 管理API的共享状态，爬取同一时间只运行一个，健康检查由代理池保证同一时间只运行一个
+#[derive(Clone)]
+struct Admin {
+    server: SocksServer,
+    crawling: Arc<AtomicBool>,
+}
+
+// 绑定管理API的监听地址并在后台处理请求，返回实际监听的地址
+// 访问令牌在每个请求上按当前配置检查，修改令牌后重载配置即可生效
+pub async fn start(server: SocksServer) -> Result<SocketAddr> {
+    let addr = server.get_config().admin.bind_addr();
+    let listener = TcpListener::bind(&addr).await
+        .map_err(|e| anyhow::anyhow!("管理API监听{}失败: {}", addr, e))?;
+    let local_addr = listener.local_addr()?;
+    let http = Server::from_tcp(listener.into_std()?)?;
+
+    let admin = Admin {
+        server,
+        crawling: Arc::new(AtomicBool::new(false)),
+    };
+    let make_service = make_service_fn(move |_| {
+        let admin = admin.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let admin = admin.clone();
+                async move { Ok::<_, Infallible>(admin.handle(request).await) }
+            }))
+        }
+    });
+    tokio::spawn(async move {
+        if let Err(e) = http.serve(make_service).await {
+            eprintln!("{} {}", "管理API错误:".red().bold(), e);
+        }
+    });
+    Ok(local_addr)
+}
+
+fn respond(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn error(status: StatusCode, message: impl std::fmt::Display) -> Response<Body> {
+    respond(status, json!({ "error": message.to_string() }))
+}
+
+async fn read_body(body: &mut Body) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    while let Some(chunk) = body.data().await {
+        content.extend_from_slice(&chunk?);
+        if content.len() > MAX_BODY_SIZE {
+            return Err(anyhow::anyhow!("请求体过长"));
+        }
+    }
+    Ok(content)
+}
+
+// 代理条目及其在代理数据库中的检测统计
+async fn proxies_json(pool: &ProxyPool, entries: &[ProxyEntry]) -> Result<Value> {
+    let mut records = Vec::with_capacity(entries.len());
+    for entry in entries {
+        records.push(pool.get_store().get(&entry.address).await);
+    }
+    export::json_value(entries, &records)
+}
+
+async fn proxy_json(pool: &ProxyPool, entry: Option<ProxyEntry>) -> Result<Value> {
+    match entry {
+        Some(entry) => Ok(proxies_json(pool, std::slice::from_ref(&entry)).await?[0].take()),
+        None => Ok(Value::Null),
+    }
+}
+
+impl Admin {
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        let config = self.server.get_config();
+        let public = config.admin.metrics_public
+            && request.method() == Method::GET
+            && request.uri().path().trim_end_matches('/') == "/metrics";
+        let expected = format!("Bearer {}", config.admin.token.expose());
+        let authorized = request.headers().get("Authorization")
+            .is_some_and(|value| constant_time_eq(value.as_bytes(), expected.as_bytes()));
+        if !public && (config.admin.token.is_empty() || !authorized) {
+            let mut response = error(StatusCode::UNAUTHORIZED, "访问令牌无效");
+            response.headers_mut().insert("WWW-Authenticate", "Bearer".parse().unwrap());
+            return response;
+        }
+
+        match self.route(request).await {
+            Ok(response) => response,
+            Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e),
+        }
+    }
+
+    async fn route(&self, mut request: Request<Body>) -> Result<Response<Body>> {
+        let pool = self.server.get_proxy_pool();
+        let path = request.uri().path().trim_end_matches('/').to_string();
+        let segments: Vec<&str> = path.split('/').skip(1).collect();
+
+        let response = match (request.method(), segments.as_slice()) {
+            (&Method::GET, ["proxies"]) => {
+                let proxies = pool.list_proxies().await;
+                let quarantine = pool.list_quarantine().await;
+                respond(StatusCode::OK, json!({
+                    "proxies": proxies_json(pool, &proxies).await?,
+                    "quarantine": proxies_json(pool, &quarantine).await?,
+                }))
+            }
+            (&Method::POST, ["proxies"]) => {
+                let body = match read_body(request.body_mut()).await {
+                    Ok(body) => body,
+                    Err(e) => return Ok(error(StatusCode::PAYLOAD_TOO_LARGE, e)),
+                };
+                self.add_proxies(&body).await?
+            }
+            (&Method::DELETE, ["proxies", address]) => {
+                let address = percent_decode(address);
+                if pool.remove_proxy(&address).await? {
+                    respond(StatusCode::OK, json!({ "removed": address }))
+                } else {
+                    error(StatusCode::NOT_FOUND, format!("代理不存在: {}", address))
+                }
+            }
+            (&Method::GET, ["current"]) => {
+                respond(StatusCode::OK, json!({ "current": proxy_json(pool, pool.get_current_proxy().await).await? }))
+            }
+            (&Method::POST, ["switch"]) => match pool.next_proxy().await {
+                Some(proxy) => respond(StatusCode::OK, json!({ "current": proxy_json(pool, Some(proxy)).await? })),
+                None => error(StatusCode::SERVICE_UNAVAILABLE, "没有可用的代理"),
+            },
+            (&Method::POST, ["goto", index]) => match index.parse::<usize>() {
+                Ok(index) if index > 0 => match pool.choose_proxy(index).await {
+                    Some(proxy) => respond(StatusCode::OK, json!({ "current": proxy_json(pool, Some(proxy)).await? })),
+                    None => error(StatusCode::SERVICE_UNAVAILABLE, "没有可用的代理"),
+                },
+                _ => error(StatusCode::BAD_REQUEST, format!("序号必须是大于0的整数: {}", index)),
+            },
+            (&Method::POST, ["health-check"]) => self.start_health_check(),
+            (&Method::POST, ["crawl"]) => self.start_crawl(),
+            (&Method::GET, ["stats"]) => respond(StatusCode::OK, self.stats().await?),
+            (&Method::GET, ["metrics"]) => Response::builder()
+                .header("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
+                .body(Body::from(metrics::render(&self.server).await))?,
+            (_, ["proxies"] | ["proxies", _] | ["current"] | ["switch"] | ["goto", _] | ["health-check"] | ["crawl"] | ["stats"] | ["metrics"]) => {
+                error(StatusCode::METHOD_NOT_ALLOWED, format!("不支持的请求方法: {}", request.method()))
+            }
+            _ => error(StatusCode::NOT_FOUND, format!("未知的接口: {}", path)),
+        };
+        Ok(response)
+    }
+
+    // 请求体为代理地址数组或 {"proxies": [...]}，地址格式与代理文件中的行相同
+    async fn add_proxies(&self, body: &[u8]) -> Result<Response<Body>> {
+        let value: Value = match serde_json::from_slice(body) {
+            Ok(value) => value,
+            Err(e) => return Ok(error(StatusCode::BAD_REQUEST, format!("请求体不是有效的JSON: {}", e))),
+        };
+        let lines = match value.get("proxies").unwrap_or(&value).as_array() {
+            Some(lines) => lines.clone(),
+            None => return Ok(error(StatusCode::BAD_REQUEST, "请求体必须是代理地址数组或 {\"proxies\": [...]}")),
+        };
+
+        let mut entries = Vec::new();
+        let mut invalid = Vec::new();
+        for line in lines {
+            match line.as_str().and_then(parse_proxy_line) {
+                Some(entry) => entries.push(entry),
+                None => invalid.push(line),
+            }
+        }
+        if !invalid.is_empty() {
+            return Ok(respond(StatusCode::BAD_REQUEST, json!({ "error": "无法解析的代理地址", "invalid": invalid })));
+        }
+        if entries.is_empty() {
+            return Ok(error(StatusCode::BAD_REQUEST, "没有要添加的代理"));
+        }
+
+        let tested = entries.len();
+        let added = self.server.get_proxy_pool().add_proxies(entries, "API添加测试").await?;
+        Ok(respond(StatusCode::OK, json!({ "tested": tested, "added": added })))
+    }
+
+    // 在后台运行健康检查，定时健康检查或其他健康检查正在运行时返回409
+    fn start_health_check(&self) -> Response<Body> {
+        if !self.server.get_proxy_pool().spawn_health_check() {
+            return error(StatusCode::CONFLICT, "健康检查正在运行");
+        }
+        respond(StatusCode::ACCEPTED, json!({ "status": "started" }))
+    }
+
+    // 在后台从所有已启用的代理源爬取，已有爬取在运行时返回409
+    fn start_crawl(&self) -> Response<Body> {
+        if !self.server.get_config().has_enabled_sources() {
+            return error(StatusCode::BAD_REQUEST, "没有已启用的代理源");
+        }
+        if self.crawling.swap(true, Ordering::SeqCst) {
+            return error(StatusCode::CONFLICT, "爬取正在运行");
+        }
+        let pool = Arc::clone(self.server.get_proxy_pool());
+        let running = Arc::clone(&self.crawling);
+        tokio::spawn(async move {
+            match crawler::crawl_enabled(&pool).await {
+                Ok((fetched, added)) => println!("{} {} {} {} {}",
+                    "[API爬取]".blue().bold(),
+                    "获取:".green(),
+                    fetched.to_string().yellow().bold(),
+                    "新增可用:".green(),
+                    added.to_string().yellow().bold()
+                ),
+                Err(e) => eprintln!("{} {}", "[API爬取] 爬取失败:".red().bold(), e),
+            }
+            running.store(false, Ordering::SeqCst);
+        });
+        respond(StatusCode::ACCEPTED, json!({ "status": "started" }))
+    }
+
+    async fn stats(&self) -> Result<Value> {
+        let pool = self.server.get_proxy_pool();
+        let listeners: Vec<Value> = self.server.listeners().iter()
+            .map(|listener| json!({
+                "name": listener.display_name(),
+                "protocol": listener.protocol,
+                "bind": listener.bind_addr(),
+                "strategy": listener.strategy,
+            }))
+            .collect();
+        Ok(json!({
+            "pool": {
+                "available": pool.list_proxies().await.len(),
+                "quarantined": pool.list_quarantine().await.len(),
+                "current": pool.get_current_proxy().await.map(|proxy| proxy.address),
+            },
+            "connections": {
+                "active": self.server.active_connections(),
+                "total": self.server.total_connections(),
+                "rejected": self.server.rejected_connections(),
+            },
+            "listeners": listeners,
+            "health_checking": pool.is_health_checking(),
+            "crawling": self.crawling.load(Ordering::SeqCst),
+            "sources": pool.get_store().source_yield().await,
+            "usage": self.server.usage().snapshot(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    async fn start_admin(token: &str, metrics_public: bool) -> String {
+        let mut config = Config::default();
+        config.proxy.db_file = String::new();
+        config.traffic.usage_file = String::new();
+        config.admin.bind_port = 0;
+        config.admin.token = token.into();
+        config.admin.metrics_public = metrics_public;
+        let addr = start(SocksServer::new(config)).await.unwrap();
+        format!("http://{}", addr)
+    }
+
+    async fn status(url: &str, method: Method, path: &str, token: Option<&str>) -> u16 {
+        let mut request = reqwest::Client::new().request(method, format!("{}{}", url, path));
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        request.send().await.unwrap().status().as_u16()
+    }
+
+    // 请求方法、路径、访问令牌、期望的状态码
+    type Case = (Method, &'static str, Option<&'static str>, u16);
+
+    #[tokio::test]
+    async fn checks_token_and_routes_requests() {
+        let url = start_admin("secret", true).await;
+        let cases: &[Case] = &[
+            (Method::GET, "/stats", None, 401),
+            (Method::GET, "/stats", Some("wrong"), 401),
+            (Method::GET, "/stats", Some("secret"), 200),
+            (Method::GET, "/proxies", None, 401),
+            // metrics_public只对GET /metrics免令牌
+            (Method::GET, "/metrics", None, 200),
+            (Method::GET, "/metrics/", None, 200),
+            (Method::POST, "/metrics", None, 401),
+            (Method::POST, "/metrics", Some("secret"), 405),
+            (Method::POST, "/goto/0", Some("secret"), 400),
+            (Method::POST, "/goto/first", Some("secret"), 400),
+            (Method::GET, "/goto/1", Some("secret"), 405),
+            (Method::DELETE, "/stats", Some("secret"), 405),
+            (Method::GET, "/unknown", Some("secret"), 404),
+            (Method::GET, "/unknown", None, 401),
+        ];
+        for (method, path, token, expected) in cases {
+            assert_eq!(status(&url, method.clone(), path, *token).await, *expected, "{} {} {:?}", method, path, token);
+        }
+
+        let response = reqwest::get(format!("{}/stats", url)).await.unwrap();
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+    }
+
+    #[tokio::test]
+    async fn rejects_every_request_without_configured_token() {
+        let url = start_admin("", false).await;
+        assert_eq!(status(&url, Method::GET, "/stats", Some("")).await, 401);
+        assert_eq!(status(&url, Method::GET, "/stats", None).await, 401);
+        assert_eq!(status(&url, Method::GET, "/metrics", None).await, 401);
+
+        let url = start_admin("secret", false).await;
+        assert_eq!(status(&url, Method::GET, "/metrics", None).await, 401);
+        assert_eq!(status(&url, Method::GET, "/metrics", Some("secret")).await, 200);
+    }
+}
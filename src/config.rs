@@ -1,26 +1,70 @@
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use anyhow::Result;
+use colored::*;
+use serde_json::Value;
+use tokio::sync::watch;
+use ipnet::IpNet;
+use std::net::IpAddr;
+use crate::users::DestinationRule;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
+    #[serde(default)]
     pub server: ServerConfig,
+    #[serde(default)]
     pub proxy: ProxyConfig,
+    #[serde(default)]
     pub log: LogConfig,
-    pub fofa: FofaConfig,
-    pub quake: QuakeConfig,
-    pub hunter: HunterConfig,
+    #[serde(default)]
+    pub access: AccessConfig,
+    #[serde(default)]
+    pub destination: DestinationConfig,
+    #[serde(default)]
+    pub traffic: TrafficConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub crawler: CrawlerConfig,
+    #[serde(default)]
+    pub sources: Vec<SourceConfig>,
+    // 多个监听器，未配置时使用[server]的监听地址与[proxy]中的认证配置
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listeners: Vec<ListenerConfig>,
+    // 旧版的独立代理源配置，加载时会转换为sources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fofa: Option<FofaConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quake: Option<QuakeConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hunter: Option<HunterConfig>,
 }
 
+// 各配置节缺少的配置项使用与默认配置文件相同的默认值
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct ServerConfig {
     pub bind_host: String,
     pub bind_port: u16,
     pub max_connections: usize,
 }
 
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_host: "127.0.0.1".to_string(),
+            bind_port: 1080,
+            max_connections: 100,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct ProxyConfig {
     pub proxy_file: String,
     pub test_timeout: u64,
@@ -32,38 +76,548 @@ pub struct ProxyConfig {
     pub max_concurrency: usize,
     pub use_auth: bool,          // 是否使用代理认证
     pub username: String,        // 代理认证用户名
-    pub password: String,        // 代理认证密码
+    pub password: Secret,        // 代理认证密码
+    pub db_file: String,         // 代理数据库文件，为空时不持久化
+    pub db_max_age: u64,         // 超过该天数未检测的代理从数据库中删除，0表示不删除
+    pub min_pool_size: usize,    // 可用代理数低于该值时立即补充代理，0表示不启用
+    pub import_files: Vec<String>, // 启动时额外导入的代理文件(Clash、订阅、CSV、JSON)，不会被改写
+    pub judge_url: String,       // 回显请求头的地址，用于判断代理匿名度，为空时不判断
+    pub users_file: String,      // 多用户认证的用户文件，开启认证的监听器同时接受其中的用户
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        ProxyConfig {
+            proxy_file: "proxies.txt".to_string(),
+            test_timeout: 5,
+            health_check_switch: true,
+            health_check_interval: 300,
+            retry_times: 3,
+            auto_switch: false,
+            switch_interval: 300,
+            max_concurrency: 100,
+            use_auth: false,
+            username: String::new(),
+            password: Secret::default(),
+            db_file: "proxy_db.json".to_string(),
+            db_max_age: 30,
+            min_pool_size: 0,
+            import_files: Vec::new(),
+            judge_url: String::new(),
+            users_file: String::new(),
+        }
+    }
+}
+
+// 监听器接受的代理协议
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ListenerProtocol {
+    #[default]
+    Socks5,
+    Http,  // HTTP代理，支持CONNECT与普通HTTP请求
+    Mixed, // 根据客户端发送的第一个字节区分SOCKS5与HTTP
 }
 
+impl ListenerProtocol {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ListenerProtocol::Socks5 => "SOCKS5",
+            ListenerProtocol::Http => "HTTP",
+            ListenerProtocol::Mixed => "SOCKS5/HTTP",
+        }
+    }
+}
+
+// 为每个连接选择上游代理的策略
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BalanceStrategy {
+    #[default]
+    Sticky,        // 使用当前选中的代理，跟随next/goto与自动切换
+    RoundRobin,    // 依次轮换可用代理
+    Random,        // 随机选择可用代理
+    LowestLatency, // 选择延迟最低的代理
+}
+
+// 监听器配置，对应配置文件中的[[listeners]]
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ListenerConfig {
+    pub name: String,             // 监听器名称，为空时使用监听地址
+    pub bind_host: String,
+    pub bind_port: u16,
+    pub protocol: ListenerProtocol, // socks5 / http / mixed
+    pub use_auth: bool,
+    pub username: String,
+    pub password: Secret,
+    pub strategy: BalanceStrategy, // sticky / round_robin / random / lowest_latency
+    pub tags: Vec<String>,        // 只使用含有其中任一标签的代理，为空时不限制
+    pub max_latency: u64,         // 只使用延迟不超过该值(毫秒)的代理，0表示不限制
+    pub anonymity: String,        // 只使用匿名度不低于该级别的代理，为空时不限制
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        ListenerConfig {
+            name: String::new(),
+            bind_host: "127.0.0.1".to_string(),
+            bind_port: 0,
+            protocol: ListenerProtocol::default(),
+            use_auth: false,
+            username: String::new(),
+            password: Secret::default(),
+            strategy: BalanceStrategy::default(),
+            tags: Vec::new(),
+            max_latency: 0,
+            anonymity: String::new(),
+        }
+    }
+}
+
+impl ListenerConfig {
+    pub fn bind_addr(&self) -> String {
+        if self.bind_host.contains(':') {
+            format!("[{}]:{}", self.bind_host, self.bind_port)
+        } else {
+            format!("{}:{}", self.bind_host, self.bind_port)
+        }
+    }
+
+    pub fn display_name(&self) -> String {
+        if self.name.is_empty() {
+            self.bind_addr()
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+// 密码、API key等敏感配置值，打印、输出日志或序列化时隐藏内容
+// 需要原值时(比较重载前后的配置变化、把配置写回磁盘)在with_exposed_secrets中序列化
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+thread_local! {
+    static EXPOSE_SECRETS: Cell<bool> = const { Cell::new(false) };
+}
+
+// 在f执行期间序列化Secret时输出原值，只用于不会离开本进程或写入配置文件的序列化
+pub fn with_exposed_secrets<T>(f: impl FnOnce() -> T) -> T {
+    struct Restore(bool);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            EXPOSE_SECRETS.with(|expose| expose.set(self.0));
+        }
+    }
+    let _restore = Restore(EXPOSE_SECRETS.with(|expose| expose.replace(true)));
+    f()
+}
+
+impl Serialize for Secret {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        if EXPOSE_SECRETS.with(Cell::get) {
+            serializer.serialize_str(&self.0)
+        } else {
+            serializer.serialize_str(&self.to_string())
+        }
+    }
+}
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // 以恒定时间比较，用于校验客户端提交的密码与令牌
+    pub fn matches(&self, given: &str) -> bool {
+        constant_time_eq(given.as_bytes(), self.0.as_bytes())
+    }
+}
+
+// 逐字节比较全部内容，比较耗时与内容无关
+pub fn constant_time_eq(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len()
+        && given.iter().zip(expected).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Secret(value.to_string())
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(value)
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\"", self)
+    }
+}
+
+impl std::fmt::Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            f.write_str("******")
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct LogConfig {
     pub show_connection_log: bool,
     pub show_error_log: bool,
 }
 
+// 客户端访问控制，所有监听器在接受连接后立即按客户端IP检查
+// 网段可以写成 192.168.0.0/16 或单个IP
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AccessConfig {
+    pub allow: Vec<String>,      // 允许连接的客户端网段，为空时不限制
+    pub deny: Vec<String>,       // 拒绝连接的客户端网段，优先于allow
+    pub no_auth: Vec<String>,    // 免认证的客户端网段，其余客户端仍按监听器配置认证
+    pub log_rejected: bool,      // 是否记录被拒绝的连接
+}
+
+impl Default for AccessConfig {
+    fn default() -> Self {
+        AccessConfig {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            no_auth: Vec::new(),
+            log_rejected: true,
+        }
+    }
+}
+
+// 客户端IP的检查结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAccess {
+    Denied,
+    Allowed,
+    NoAuth, // 允许连接且免认证
+}
+
+// 解析网段或单个IP
+pub fn parse_net(value: &str) -> Option<IpNet> {
+    let value = value.trim();
+    value.parse::<IpNet>().ok()
+        .or_else(|| value.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+fn in_nets(nets: &[String], ip: IpAddr) -> bool {
+    nets.iter().filter_map(|net| parse_net(net)).any(|net| net.contains(&ip))
+}
+
+impl AccessConfig {
+    pub fn check(&self, ip: IpAddr) -> ClientAccess {
+        // 监听IPv6地址时IPv4客户端显示为 ::ffff:a.b.c.d
+        let ip = ip.to_canonical();
+        if in_nets(&self.deny, ip) || (!self.allow.is_empty() && !in_nets(&self.allow, ip)) {
+            ClientAccess::Denied
+        } else if in_nets(&self.no_auth, ip) {
+            ClientAccess::NoAuth
+        } else {
+            ClientAccess::Allowed
+        }
+    }
+}
+
+// 目标地址策略，防止通过代理访问内网与云服务器元数据地址
+// 规则格式与用户文件中的allow/deny相同，例如 10.0.0.0/8、*.internal、*:25
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DestinationConfig {
+    pub block_private: bool,     // 拒绝私有网段、回环、链路本地地址与localhost
+    pub deny: Vec<String>,       // 额外拒绝的目标
+    pub allow: Vec<String>,      // 允许的目标，优先于block_private与deny
+}
+
+impl Default for DestinationConfig {
+    fn default() -> Self {
+        DestinationConfig {
+            block_private: true,
+            deny: Vec::new(),
+            allow: Vec::new(),
+        }
+    }
+}
+
+fn matches_rules(rules: &[String], host: &str, port: u16) -> bool {
+    rules.iter()
+        .filter_map(|rule| DestinationRule::parse(rule).ok())
+        .any(|rule| rule.matches(host, port))
+}
+
+// 私有网段(RFC1918、IPv6唯一本地地址)、回环、链路本地、本网络(0.0.0.0/8)、
+// 运营商级NAT(100.64.0.0/10，包含100.100.100.200等云服务器元数据地址)与广播地址
+fn is_internal_ip(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_broadcast()
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64)
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            ip.is_loopback() || ip.is_unspecified()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+// 按inet_aton的规则解析IPv4地址，支持十进制、八进制(0开头)、十六进制(0x开头)与省略的写法，
+// 例如 127.1、2130706433、0x7f000001、0177.0.0.1，上游代理按域名解析这些写法时会得到对应的IP
+fn parse_inet_aton(host: &str) -> Option<std::net::Ipv4Addr> {
+    let parts: Vec<u32> = host.split('.')
+        .map(|part| {
+            let (digits, radix) = if let Some(hex) = part.strip_prefix("0x").or_else(|| part.strip_prefix("0X")) {
+                (hex, 16)
+            } else if part.len() > 1 && part.starts_with('0') {
+                (&part[1..], 8)
+            } else {
+                (part, 10)
+            };
+            match digits {
+                "" if radix == 16 => Some(0),
+                "" => None,
+                _ => u32::from_str_radix(digits, radix).ok(),
+            }
+        })
+        .collect::<Option<_>>()?;
+    let (last, leading) = parts.split_last()?;
+    if leading.len() > 3 || leading.iter().any(|&part| part > 0xff) {
+        return None;
+    }
+    // 最后一部分填满剩余的字节，例如 127.1 中的1占三个字节
+    let bits = 8 * (4 - leading.len() as u32);
+    if bits < 32 && *last >> bits != 0 {
+        return None;
+    }
+    let value = leading.iter().enumerate().fold(*last, |value, (i, &part)| value | (part << (24 - 8 * i)));
+    Some(value.into())
+}
+
+// 全部由数字组成(允许0x前缀与点分隔)的主机名，无法按IPv4解析时同样不允许作为域名访问
+fn is_numeric_host(host: &str) -> bool {
+    !host.is_empty() && host.split('.').all(|part| {
+        let digits = part.strip_prefix("0x").unwrap_or(part);
+        let hex = digits.len() < part.len() && digits.bytes().all(|b| b.is_ascii_hexdigit());
+        !part.is_empty() && (hex || part.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+// 目标主机的规范写法，数字形式的IPv4地址转换为点分十进制
+fn normalize_host(host: &str) -> String {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    match parse_inet_aton(&host) {
+        Some(ip) if host.parse::<IpAddr>().is_err() => ip.to_string(),
+        _ => host,
+    }
+}
+
+impl DestinationConfig {
+    // 域名由上游代理解析，这里不做解析，只检查IP形式(含inet_aton的各种数字写法)的目标与localhost
+    pub fn allows(&self, host: &str, port: u16) -> bool {
+        let host = normalize_host(host);
+        if matches_rules(&self.allow, &host, port) {
+            return true;
+        }
+        if matches_rules(&self.deny, &host, port) {
+            return false;
+        }
+        if self.block_private {
+            let internal = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+                Ok(ip) => is_internal_ip(ip),
+                Err(_) => host == "localhost" || host.ends_with(".localhost") || is_numeric_host(&host),
+            };
+            if internal {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// 按客户端IP的限速与流量配额，用户的限速与配额在用户文件中配置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TrafficConfig {
+    pub client_bandwidth_limit: u64, // 每个客户端IP所有连接合计的带宽上限(KB/s)，0表示不限制
+    pub client_daily_quota: u64,     // 每个客户端IP每天的流量配额(MB)，0表示不限制
+    pub client_monthly_quota: u64,   // 每个客户端IP每月的流量配额(MB)，0表示不限制
+    pub usage_file: String,          // 流量统计文件，为空时不持久化
+}
+
+impl Default for TrafficConfig {
+    fn default() -> Self {
+        TrafficConfig {
+            client_bandwidth_limit: 0,
+            client_daily_quota: 0,
+            client_monthly_quota: 0,
+            usage_file: "usage.json".to_string(),
+        }
+    }
+}
+
+// HTTP管理API，供systemd或容器中无法使用REPL时管理代理池
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AdminConfig {
+    pub enabled: bool,
+    pub bind_host: String,
+    pub bind_port: u16,
+    pub token: Secret,           // 访问令牌，请求需带 Authorization: Bearer <token>
+    pub metrics_public: bool,    // GET /metrics是否免令牌，便于Prometheus直接抓取
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        AdminConfig {
+            enabled: false,
+            bind_host: "127.0.0.1".to_string(),
+            bind_port: 9090,
+            token: Secret::default(),
+            metrics_public: false,
+        }
+    }
+}
+
+impl AdminConfig {
+    pub fn bind_addr(&self) -> String {
+        format!("{}:{}", self.bind_host, self.bind_port)
+    }
+}
+
+// 爬取结果的输出方式
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CrawlerConfig {
+    #[serde(default)]
+    pub staging_file: String,    // 爬取结果写入的暂存文件，为空时合并到proxy_file
+    #[serde(default)]
+    pub dry_run: bool,           // 只输出将要发生的变化，不写入任何文件
+}
+
+// 代理源配置，对应配置文件中的[[sources]]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SourceConfig {
+    #[serde(rename = "type", default)]
+    pub kind: String,            // 代理源类型: fofa / quake / hunter / url / shodan / zoomeye / censys
+    #[serde(default)]
+    pub name: String,            // 代理源名称，为空时使用类型名
+    #[serde(default, alias = "switch")]
+    pub enabled: bool,
+    #[serde(default, alias = "url")]
+    pub api_url: String,         // 为空时使用该类型的默认API地址，url类型为列表地址
+    #[serde(default)]
+    pub key: Secret,
+    #[serde(default)]
+    pub query_str: String,
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>, // 查询语句中的自定义变量，例如 country = "CN"
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub page_size: u64,          // 每页条数，0表示使用该源的默认值
+    #[serde(default)]
+    pub rate_limit_ms: u64,      // 相邻请求的最小间隔(毫秒)，0表示使用该源的默认值
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,        // 网络错误、429或5xx时的最大重试次数
+    #[serde(default)]
+    pub quota_budget: u64,       // 单次爬取允许消耗的配额(该API的计费单位)，0表示不限制
+    #[serde(default)]
+    pub crawl_interval: u64,     // 后台定时爬取间隔(秒)，0表示不定时爬取
+    #[serde(default)]
+    pub format: String,          // url类型的列表格式: text / json / clash / subscription / csv，为空时自动识别
+    #[serde(default)]
+    pub json_path: String,       // json格式时代理列表所在路径，例如 data.proxies[*].addr
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+impl Default for SourceConfig {
+    fn default() -> Self {
+        SourceConfig {
+            kind: String::new(),
+            name: String::new(),
+            enabled: false,
+            api_url: String::new(),
+            key: Secret::default(),
+            query_str: String::new(),
+            vars: BTreeMap::new(),
+            size: 0,
+            page_size: 0,
+            rate_limit_ms: 0,
+            max_retries: default_max_retries(),
+            quota_budget: 0,
+            crawl_interval: 0,
+            format: String::new(),
+            json_path: String::new(),
+        }
+    }
+}
+
+impl SourceConfig {
+    pub fn display_name(&self) -> &str {
+        if self.name.is_empty() {
+            &self.kind
+        } else {
+            &self.name
+        }
+    }
+
+    pub fn api_url_or<'a>(&'a self, default: &'a str) -> &'a str {
+        if self.api_url.is_empty() {
+            default
+        } else {
+            &self.api_url
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct FofaConfig {
     pub switch: bool,
     pub api_url: String,
-    pub fofa_key: String,
+    pub fofa_key: Secret,
     pub query_str: String,
     pub size: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct QuakeConfig {
     pub switch: bool,
     pub api_url: String,
-    pub quake_key: String,
+    pub quake_key: Secret,
     pub query_str: String,
     pub size: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct HunterConfig {
     pub switch: bool,
     pub api_url: String,
-    pub hunter_key: String,
+    pub hunter_key: Secret,
     pub query_str: String,
     pub size: u64,
 }
@@ -74,43 +628,103 @@ bind_host = "127.0.0.1"
 bind_port = 1080
 max_connections = 100
 
+# 多个监听器，配置[[listeners]]后不再使用上面的bind_host/bind_port与[proxy]中的认证配置
+# [[listeners]]
+# name = "team-a"
+# bind_port = 1081
+# protocol = "mixed"         # socks5 / http / mixed(同一端口同时接受SOCKS5与HTTP)
+# use_auth = true
+# username = "a"
+# password = ""              # 也可用 password_file 从文件读取
+# strategy = "round_robin"   # sticky / round_robin / random / lowest_latency
+# tags = ["hk"]              # 只使用含有其中任一标签的代理
+# max_latency = 0            # 只使用延迟不超过该值(毫秒)的代理，0表示不限制
+# anonymity = ""             # 只使用匿名度不低于该级别的代理
+
 [proxy]
 proxy_file = "proxies.txt"
 test_timeout = 5
 health_check_switch = true  # 是否启用健康检查
 health_check_interval = 300 # 健康检测间隔(秒)
-retry_times = 3
+retry_times = 3           # 健康检查连续失败次数达到该值后丢弃代理
 auto_switch = false        # 是否开启自动切换代理
 switch_interval = 300      # 自动切换间隔(秒)
 max_concurrency = 100     # 最大并发测试数
 use_auth = false          # 是否使用代理认证
 username = ""             # 代理认证用户名
-password = ""             # 代理认证密码
+password = ""             # 代理认证密码，也可用 password_file 从文件读取
+db_file = "proxy_db.json" # 代理数据库文件，保存检测历史，为空时不持久化
+db_max_age = 30           # 超过该天数未检测的代理从数据库中删除，0表示不删除
+min_pool_size = 0         # 可用代理数低于该值时立即爬取并复测隔离区，0表示不启用
+import_files = []         # 额外导入的代理文件，支持Clash配置、订阅、CSV与JSON，按扩展名和内容自动识别
+judge_url = ""            # 回显请求头的地址(例如 http://httpbin.org/get)，用于判断代理匿名度，为空时不判断
+users_file = ""           # 多用户认证的用户文件(例如 users.toml)，为空时只使用username/password
 
 [log]
 show_connection_log = false  # 设置为 false 可以关闭连接日志
 show_error_log = false      # 设置为 false 可以关闭错误日志
 
-[fofa]
-switch = false
+[access]
+allow = []                 # 允许连接的客户端网段(例如 "192.168.0.0/16")，为空时不限制，监听0.0.0.0时建议配置
+deny = []                  # 拒绝连接的客户端网段，优先于allow
+no_auth = []               # 免认证的客户端网段，例如局域网客户端免认证、其余客户端需要认证
+log_rejected = true        # 是否记录被拒绝的连接
+
+[destination]
+block_private = true       # 拒绝访问私有网段、回环、链路本地、100.64.0.0/10等元数据地址与localhost
+deny = []                  # 额外拒绝的目标，例如 "100.64.0.0/10"、"*.internal"、"*:25"
+allow = []                 # 允许的目标，优先于以上规则，例如 "192.168.1.10:8080"
+
+[traffic]
+client_bandwidth_limit = 0 # 每个客户端IP所有连接合计的带宽上限(KB/s)，0表示不限制
+client_daily_quota = 0     # 每个客户端IP每天的流量配额(MB)，0表示不限制，用户的配额在用户文件中配置
+client_monthly_quota = 0   # 每个客户端IP每月的流量配额(MB)，0表示不限制
+usage_file = "usage.json"  # 流量统计文件，重启后继续累计，为空时不持久化
+
+[admin]
+enabled = false            # 是否启用HTTP管理API
+bind_host = "127.0.0.1"    # 管理API监听地址，与代理监听地址分开
+bind_port = 9090           # 管理API监听端口
+token = ""                 # 访问令牌，请求需带 Authorization: Bearer <token>，也可用 token_file 从文件读取
+metrics_public = false     # GET /metrics是否免令牌，关闭时Prometheus需配置 authorization: { credentials: <token> }
+
+[crawler]
+staging_file = ""          # 爬取结果写入的暂存文件，为空时合并到proxy_file
+dry_run = false            # 只输出爬取将要新增的代理，不写入任何文件
+
+# 代理源，可配置多个[[sources]]，type可选: fofa / quake / hunter / url / shodan / zoomeye / censys
+[[sources]]
+type = "fofa"
+enabled = false
 api_url = 'https://fofa.info/api/v1/search/all'
-fofa_key = '186******f8a******6a92******4abf1c' # 替换成自己的key
-query_str = '(protocol=="socks5" && country="CN" && banner="Method:No Authentication") && after="2025-02-25"' # 这里可以用after添加时间限制，过滤不可用的代理
+key = '186******f8a******6a92******4abf1c' # 替换成自己的key，也可用 key_file 从文件读取
+query_str = '(protocol=="socks5" && country="{{country}}" && banner="Method:No Authentication") && after="{{today-30d}}"' # 支持{{today-7d}}、{{now}}等占位符，每次爬取时渲染，after只保留近期活跃的代理
+vars = { country = "CN" } # 查询语句中的自定义变量
 size = 10000 # 这里是获取的条数
+page_size = 1000 # 每页条数，会自动翻页直到获取够size条
+rate_limit_ms = 0 # 相邻请求的最小间隔(毫秒)，0表示使用默认值
+max_retries = 3 # 网络错误、429或5xx时的最大重试次数
+quota_budget = 0 # 单次爬取允许消耗的配额，预计消耗超出时拒绝爬取，0表示不限制
+crawl_interval = 0 # 后台定时爬取间隔(秒)，0表示不定时爬取
 
-[quake]
-switch = false
+[[sources]]
+type = "quake"
+enabled = false
 api_url = 'https://quake.360.net/api/v3/search/quake_service'
-quake_key = '0e****-3***-4***-a***-5a21********' # 替换成自己的key
+key = '0e****-3***-4***-a***-5a21********' # 替换成自己的key
 query_str = 'service:socks5 AND country: "CN" AND response:"No authentication"'
 size = 500 # 这里是获取的条数
+page_size = 100 # 每页条数，最大500
+crawl_interval = 0
 
-[hunter]
-switch = false
+[[sources]]
+type = "hunter"
+enabled = false
 api_url = 'https://hunter.qianxin.com/openApi/search'
-hunter_key = '365*******9ab9*******b0f0*******d1cd0d3399' # 替换成自己的key
+key = '365*******9ab9*******b0f0*******d1cd0d3399' # 替换成自己的key
 query_str = 'protocol=="socks5"&&protocol.banner="No authentication"&&ip.country="CN"'
 size = 4 # 这里是指页数，一页100条
+crawl_interval = 0
 "#;
 
 impl Default for Config {
@@ -123,49 +737,48 @@ impl Default for Config {
                 // 如果解析失败，打印错误并使用硬编码的备选配置
                 eprintln!("错误：无法解析默认配置字符串: {}", e);
                 Config {
-                    server: ServerConfig {
-                        bind_host: "127.0.0.1".to_string(),
-                        bind_port: 1080,
-                        max_connections: 100,
-                    },
-                    proxy: ProxyConfig {
-                        proxy_file: "proxies.txt".to_string(),
-                        test_timeout: 5,
-                        health_check_switch: true,
-                        health_check_interval: 300,
-                        retry_times: 3,
-                        auto_switch: false,
-                        switch_interval: 300,
-                        max_concurrency: 100,
-                        use_auth: false,
-                        username: String::new(),
-                        password: String::new(),
-                    },
-                    log: LogConfig {
-                        show_connection_log: false,
-                        show_error_log: false,
-                    },
-                    fofa: FofaConfig {
-                        switch: false,
-                        api_url: "https://fofa.info/api/v1/search/all".to_string(),
-                        fofa_key: "186******f8a******6a92******4abf1c".to_string(),
-                        query_str: "(protocol==\"socks5\" && country=\"CN\" && banner=\"Method:No Authentication\") && after=\"2025-02-25\"".to_string(),
-                        size: 10000,
-                    },
-                    quake: QuakeConfig {
-                        switch: false,
-                        api_url: "https://quake.360.net/api/v3/search/quake_service".to_string(),
-                        quake_key: "0e****-3***-4***-a***-5a21********".to_string(),
-                        query_str: "service:socks5 AND country: \"CN\" AND response:\"No authentication\"".to_string(),
-                        size: 500,
-                    },
-                    hunter: HunterConfig {
-                        switch: false,
-                        api_url: "https://hunter.qianxin.com/openApi/search".to_string(),
-                        hunter_key: "365*******9ab9*******b0f0*******d1cd0d3399".to_string(),
-                        query_str: "protocol==\"socks5\"&&protocol.banner=\"No authentication\"&&ip.country=\"CN\"".to_string(),
-                        size: 4,
-                    },
+                    server: ServerConfig::default(),
+                    proxy: ProxyConfig::default(),
+                    log: LogConfig::default(),
+                    access: AccessConfig::default(),
+                    destination: DestinationConfig::default(),
+                    traffic: TrafficConfig::default(),
+                    admin: AdminConfig::default(),
+                    crawler: CrawlerConfig::default(),
+                    sources: vec![
+                        SourceConfig {
+                            kind: "fofa".to_string(),
+                            enabled: false,
+                            api_url: "https://fofa.info/api/v1/search/all".to_string(),
+                            key: "186******f8a******6a92******4abf1c".into(),
+                            query_str: "(protocol==\"socks5\" && country=\"{{country}}\" && banner=\"Method:No Authentication\") && after=\"{{today-30d}}\"".to_string(),
+                            vars: BTreeMap::from([("country".to_string(), "CN".to_string())]),
+                            size: 10000,
+                            ..Default::default()
+                        },
+                        SourceConfig {
+                            kind: "quake".to_string(),
+                            enabled: false,
+                            api_url: "https://quake.360.net/api/v3/search/quake_service".to_string(),
+                            key: "0e****-3***-4***-a***-5a21********".into(),
+                            query_str: "service:socks5 AND country: \"CN\" AND response:\"No authentication\"".to_string(),
+                            size: 500,
+                            ..Default::default()
+                        },
+                        SourceConfig {
+                            kind: "hunter".to_string(),
+                            enabled: false,
+                            api_url: "https://hunter.qianxin.com/openApi/search".to_string(),
+                            key: "365*******9ab9*******b0f0*******d1cd0d3399".into(),
+                            query_str: "protocol==\"socks5\"&&protocol.banner=\"No authentication\"&&ip.country=\"CN\"".to_string(),
+                            size: 4,
+                            ..Default::default()
+                        },
+                    ],
+                    listeners: Vec::new(),
+                    fofa: None,
+                    quake: None,
+                    hunter: None,
                 }
             }
         }
@@ -174,16 +787,816 @@ impl Default for Config {
 
 impl Config {
     pub fn load() -> Result<Self> {
-        let config_path = Path::new("config.toml");
-        
-        if !config_path.exists() {
-            let config = Config::default();
+        Self::load_from("config.toml")
+    }
+
+    // 从指定路径加载配置，文件不存在时写入默认配置
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let config = Self::read(path.as_ref())?;
+        config.ensure_valid()?;
+        Ok(config)
+    }
+
+    // 读取配置文件但不检查配置值，文件不存在时写入默认配置
+    fn read(config_path: &Path) -> Result<Self> {
+        let content = if config_path.exists() {
+            fs::read_to_string(config_path)?
+        } else {
             fs::write(config_path, DEFAULT_CONFIG)?;
-            return Ok(config);
-        }
-        
-        let content = fs::read_to_string(config_path)?;
-        let config: Config = toml::from_str(&content)?;
+            DEFAULT_CONFIG.to_string()
+        };
+        let (config, unknown) = Self::parse(&content)?;
+        warn_unknown_keys(&unknown);
         Ok(config)
     }
-} 
\ No newline at end of file
+
+    // 解析配置内容，缺少的配置项使用默认值，同时返回无法识别的配置项路径
+    // LOKIPOOL_*环境变量覆盖配置文件中的值，之后读取 *_file 形式的敏感配置文件
+    pub fn parse(content: &str) -> Result<(Self, Vec<String>)> {
+        let mut raw: toml::Value = toml::from_str(content)
+            .map_err(|e| anyhow::anyhow!("解析配置文件失败: {}", e))?;
+        apply_env_overrides(&mut raw, std::env::vars())?;
+        resolve_secret_files("", &mut raw)?;
+        let config: Config = raw.clone().try_into()
+            .map_err(|e| anyhow::anyhow!("解析配置文件失败: {}", e))?;
+
+        let known = serde_json::to_value(&config)?;
+        let raw = serde_json::to_value(&raw)?;
+        let mut unknown = Vec::new();
+        find_unknown_keys("", &raw, &known, &mut unknown);
+        Ok((config, unknown))
+    }
+
+    // 检查配置值，一次返回所有问题
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |key: &str, message: &str| issues.push(ConfigIssue {
+            key: key.to_string(),
+            message: message.to_string(),
+        });
+
+        let server = &self.server;
+        if server.bind_host.trim().is_empty() {
+            issue("server.bind_host", "监听地址不能为空");
+        }
+        if server.bind_port == 0 {
+            issue("server.bind_port", "监听端口不能为0");
+        }
+        if server.max_connections == 0 {
+            issue("server.max_connections", "必须大于0");
+        }
+
+        let proxy = &self.proxy;
+        if proxy.proxy_file.trim().is_empty() {
+            issue("proxy.proxy_file", "代理文件路径不能为空");
+        }
+        if proxy.test_timeout == 0 {
+            issue("proxy.test_timeout", "必须大于0，否则所有代理测试都会超时");
+        }
+        if proxy.health_check_switch && proxy.health_check_interval == 0 {
+            issue("proxy.health_check_interval", "启用健康检查时必须大于0");
+        }
+        if proxy.retry_times == 0 {
+            issue("proxy.retry_times", "必须大于0");
+        }
+        if proxy.auto_switch && proxy.switch_interval == 0 {
+            issue("proxy.switch_interval", "开启自动切换时必须大于0");
+        }
+        if proxy.max_concurrency == 0 {
+            issue("proxy.max_concurrency", "必须大于0，否则代理测试无法进行");
+        }
+        if self.admin.enabled {
+            if self.admin.token.is_empty() {
+                issue("admin.token", "启用管理API时必须配置访问令牌");
+            }
+            if self.admin.bind_port == 0 {
+                issue("admin.bind_port", "监听端口不能为0");
+            }
+        }
+        if proxy.use_auth && proxy.username.is_empty() && proxy.users_file.is_empty() {
+            issue("proxy.username", "开启代理认证时需要配置用户名或users_file");
+        }
+        // SOCKS5用户名密码认证(RFC1929)中长度只占一个字节
+        if proxy.username.len() > 255 {
+            issue("proxy.username", "长度不能超过255字节");
+        }
+        if proxy.password.expose().len() > 255 {
+            issue("proxy.password", "长度不能超过255字节");
+        }
+        if !proxy.judge_url.is_empty() && !is_http_url(&proxy.judge_url) {
+            issue("proxy.judge_url", "必须是http或https地址");
+        }
+        for (i, file) in proxy.import_files.iter().enumerate() {
+            if file.trim().is_empty() {
+                issue(&format!("proxy.import_files[{}]", i), "文件路径不能为空");
+            }
+        }
+
+        for (field, nets) in [("allow", &self.access.allow), ("deny", &self.access.deny), ("no_auth", &self.access.no_auth)] {
+            for (i, net) in nets.iter().enumerate() {
+                if parse_net(net).is_none() {
+                    issue(&format!("access.{}[{}]", field, i), "必须是网段(例如 192.168.0.0/16)或IP地址");
+                }
+            }
+        }
+
+        for (field, rules) in [("allow", &self.destination.allow), ("deny", &self.destination.deny)] {
+            for (i, rule) in rules.iter().enumerate() {
+                if let Err(e) = DestinationRule::parse(rule) {
+                    issue(&format!("destination.{}[{}]", field, i), &e.to_string());
+                }
+            }
+        }
+
+        let mut binds = std::collections::HashSet::new();
+        for (i, listener) in self.listeners.iter().enumerate() {
+            let key = format!("listeners[{}]", i);
+            if listener.bind_host.trim().is_empty() {
+                issue(&format!("{}.bind_host", key), "监听地址不能为空");
+            }
+            if listener.bind_port == 0 {
+                issue(&format!("{}.bind_port", key), "必须配置监听端口");
+            } else if !binds.insert(listener.bind_addr()) {
+                issue(&format!("{}.bind_port", key), "与其他监听器的监听地址重复");
+            }
+            if listener.use_auth && listener.username.is_empty() && proxy.users_file.is_empty() {
+                issue(&format!("{}.username", key), "开启代理认证时需要配置用户名或proxy.users_file");
+            }
+            if listener.username.len() > 255 {
+                issue(&format!("{}.username", key), "长度不能超过255字节");
+            }
+            if listener.password.expose().len() > 255 {
+                issue(&format!("{}.password", key), "长度不能超过255字节");
+            }
+            if !listener.anonymity.is_empty() && crate::proxy_pool::Anonymity::from_name(&listener.anonymity).is_none() {
+                issue(&format!("{}.anonymity", key), "可选 transparent / anonymous / elite");
+            }
+        }
+
+        let registry = crate::crawler::SourceRegistry::default();
+        for (key, source) in self.labeled_sources() {
+            if source.kind.is_empty() {
+                issue(&format!("{}.type", key), "缺少代理源类型");
+                continue;
+            }
+            if !source.enabled {
+                continue;
+            }
+            if let Err(e) = registry.build(&source) {
+                issue(&key, &e.to_string());
+                continue;
+            }
+            if source.kind.eq_ignore_ascii_case("url") {
+                continue;
+            }
+            // 旧版配置中key的字段名为fofa_key等
+            let key_field = if key.starts_with("sources") { "key".to_string() } else { format!("{}_key", key) };
+            if source.key.is_empty() {
+                issue(&format!("{}.{}", key, key_field), "启用的代理源必须配置key");
+            }
+            if source.size == 0 {
+                issue(&format!("{}.size", key), "必须大于0，否则不会获取任何代理");
+            }
+            if !source.api_url.is_empty() && !is_http_url(&source.api_url) {
+                issue(&format!("{}.api_url", key), "必须是http或https地址");
+            }
+            if let Err(e) = crate::crawler::template::render_query(&source) {
+                issue(&format!("{}.query_str", key), &e.to_string());
+            }
+        }
+        issues
+    }
+
+    // 存在问题时返回列出全部问题的错误
+    pub fn ensure_valid(&self) -> Result<()> {
+        let issues = self.validate();
+        if issues.is_empty() {
+            return Ok(());
+        }
+        Err(issues_error("配置", &issues))
+    }
+
+    // 代理源及其在配置文件中的路径，顺序与source_configs()一致
+    fn labeled_sources(&self) -> Vec<(String, SourceConfig)> {
+        let mut labels = Vec::new();
+        if self.fofa.is_some() {
+            labels.push("fofa".to_string());
+        }
+        if self.quake.is_some() {
+            labels.push("quake".to_string());
+        }
+        if self.hunter.is_some() {
+            labels.push("hunter".to_string());
+        }
+        labels.extend((0..self.sources.len()).map(|i| format!("sources[{}]", i)));
+        labels.into_iter().zip(self.source_configs()).collect()
+    }
+
+    // 所有代理源配置，旧版[fofa]/[quake]/[hunter]配置会被转换后排在前面
+    pub fn source_configs(&self) -> Vec<SourceConfig> {
+        let mut sources = Vec::new();
+        if let Some(fofa) = &self.fofa {
+            sources.push(SourceConfig {
+                kind: "fofa".to_string(),
+                enabled: fofa.switch,
+                api_url: fofa.api_url.clone(),
+                key: fofa.fofa_key.clone(),
+                query_str: fofa.query_str.clone(),
+                size: fofa.size,
+                ..Default::default()
+            });
+        }
+        if let Some(quake) = &self.quake {
+            sources.push(SourceConfig {
+                kind: "quake".to_string(),
+                enabled: quake.switch,
+                api_url: quake.api_url.clone(),
+                key: quake.quake_key.clone(),
+                query_str: quake.query_str.clone(),
+                size: quake.size,
+                ..Default::default()
+            });
+        }
+        if let Some(hunter) = &self.hunter {
+            sources.push(SourceConfig {
+                kind: "hunter".to_string(),
+                enabled: hunter.switch,
+                api_url: hunter.api_url.clone(),
+                key: hunter.hunter_key.clone(),
+                query_str: hunter.query_str.clone(),
+                size: hunter.size,
+                ..Default::default()
+            });
+        }
+        sources.extend(self.sources.iter().cloned());
+        sources
+    }
+
+    pub fn has_enabled_sources(&self) -> bool {
+        self.source_configs().iter().any(|s| s.enabled)
+    }
+
+    // 所有监听器配置，未配置[[listeners]]时由[server]与[proxy]的认证配置组成唯一的SOCKS5监听器
+    pub fn listener_configs(&self) -> Vec<ListenerConfig> {
+        if !self.listeners.is_empty() {
+            return self.listeners.clone();
+        }
+        vec![ListenerConfig {
+            bind_host: self.server.bind_host.clone(),
+            bind_port: self.server.bind_port,
+            use_auth: self.proxy.use_auth,
+            username: self.proxy.username.clone(),
+            password: self.proxy.password.clone(),
+            ..Default::default()
+        }]
+    }
+}
+
+// 配置检查发现的问题
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    pub key: String,      // 配置项路径，例如 proxy.max_concurrency、sources[0].key
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+// 列出全部问题的错误，subject为检查的对象，例如 配置、用户文件
+pub fn issues_error(subject: &str, issues: &[ConfigIssue]) -> anyhow::Error {
+    let lines: Vec<String> = issues.iter().map(|issue| format!("  {}", issue)).collect();
+    anyhow::anyhow!("{}检查发现{}个问题:\n{}", subject, issues.len(), lines.join("\n"))
+}
+
+fn is_http_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+// 无法识别的配置项可能是拼写错误，只输出警告
+pub fn warn_unknown_keys(keys: &[String]) {
+    for key in keys {
+        eprintln!("{} {}", "警告: 无法识别的配置项，已忽略:".yellow().bold(), key);
+    }
+}
+
+// 配置文件中存在、但解析后的配置中没有对应字段的配置项
+fn find_unknown_keys(path: &str, raw: &Value, known: &Value, unknown: &mut Vec<String>) {
+    match (raw, known) {
+        (Value::Object(raw_map), Value::Object(known_map)) => {
+            for (key, value) in raw_map {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match known_map.get(key) {
+                    Some(known_value) => find_unknown_keys(&child, value, known_value, unknown),
+                    None if !KEY_ALIASES.contains(&key.as_str()) => unknown.push(child),
+                    None => {}
+                }
+            }
+        }
+        (Value::Array(raw_items), Value::Array(known_items)) => {
+            for (i, (raw_item, known_item)) in raw_items.iter().zip(known_items).enumerate() {
+                find_unknown_keys(&format!("{}[{}]", path, i), raw_item, known_item, unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+// 环境变量覆盖的前缀，变量名中用双下划线分隔配置项路径
+// 例如 LOKIPOOL_PROXY__PASSWORD、LOKIPOOL_SOURCES__0__KEY
+const ENV_PREFIX: &str = "LOKIPOOL_";
+
+// 可以通过 <名称>_file 从文件读取的敏感配置项
+const SECRET_KEYS: &[&str] = &["password", "key", "token", "fofa_key", "quake_key", "hunter_key"];
+
+// 把LOKIPOOL_*环境变量写入解析前的配置，值按默认配置中对应配置项的类型解析
+fn apply_env_overrides<I: IntoIterator<Item = (String, String)>>(raw: &mut toml::Value, vars: I) -> Result<()> {
+    let schema = config_schema()?;
+    let mut vars: Vec<(String, String)> = vars.into_iter()
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX) && name.contains("__"))
+        .collect();
+    vars.sort();
+
+    for (name, value) in vars {
+        let path: Vec<String> = name[ENV_PREFIX.len()..].split("__").map(str::to_lowercase).collect();
+        let key = path.join(".");
+        if path.iter().any(String::is_empty) {
+            return Err(anyhow::anyhow!("环境变量 {} 的配置项路径无效", name));
+        }
+        let value = env_value(schema_type(&schema, &path), &value)
+            .map_err(|e| anyhow::anyhow!("环境变量 {} 的值无效: {}", name, e))?;
+        set_path(raw, &path, value)
+            .map_err(|e| anyhow::anyhow!("环境变量 {} 无法覆盖 {}: {}", name, key, e))?;
+    }
+    Ok(())
+}
+
+// 用于确定环境变量类型的配置结构，数组与旧版配置各包含一个默认元素
+fn config_schema() -> Result<Value> {
+    let config = Config {
+        sources: vec![SourceConfig::default()],
+        listeners: vec![ListenerConfig::default()],
+        fofa: Some(FofaConfig::default()),
+        quake: Some(QuakeConfig::default()),
+        hunter: Some(HunterConfig::default()),
+        ..Config::default()
+    };
+    Ok(serde_json::to_value(config)?)
+}
+
+fn schema_type<'a>(schema: &'a Value, path: &[String]) -> Option<&'a Value> {
+    let mut current = schema;
+    for segment in path {
+        current = match current {
+            Value::Object(map) => map.get(segment)?,
+            Value::Array(items) if segment.parse::<usize>().is_ok() => items.first()?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+fn env_value(schema: Option<&Value>, value: &str) -> Result<toml::Value> {
+    match schema {
+        Some(Value::String(_)) => Ok(toml::Value::String(value.to_string())),
+        Some(Value::Bool(_)) => value.parse::<bool>()
+            .map(toml::Value::Boolean)
+            .map_err(|_| anyhow::anyhow!("应为 true 或 false")),
+        Some(Value::Number(_)) => value.parse::<i64>()
+            .map(toml::Value::Integer)
+            .map_err(|_| anyhow::anyhow!("应为整数")),
+        // 数组与表格按TOML语法解析，例如 ["a.yaml", "b.csv"]
+        Some(Value::Array(_)) | Some(Value::Object(_)) => parse_toml_value(value),
+        // 未知的配置项(例如vars中的自定义变量)优先按TOML语法解析，失败时作为字符串
+        _ => Ok(parse_toml_value(value).unwrap_or_else(|_| toml::Value::String(value.to_string()))),
+    }
+}
+
+fn parse_toml_value(value: &str) -> Result<toml::Value> {
+    let mut table: toml::Table = toml::from_str(&format!("value = {}", value))?;
+    table.remove("value").ok_or_else(|| anyhow::anyhow!("无法解析: {}", value))
+}
+
+// 按路径写入值，缺少的表格会被创建，数组下标等于数组长度时追加新元素
+fn set_path(current: &mut toml::Value, path: &[String], value: toml::Value) -> Result<()> {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            *current = value;
+            return Ok(());
+        }
+    };
+    let child = match current {
+        toml::Value::Table(table) => table.entry(segment.clone())
+            .or_insert_with(|| if rest.first().is_some_and(|next| next.parse::<usize>().is_ok()) {
+                toml::Value::Array(Vec::new())
+            } else {
+                toml::Value::Table(toml::Table::new())
+            }),
+        toml::Value::Array(items) => {
+            let index: usize = segment.parse()
+                .map_err(|_| anyhow::anyhow!("{} 不是数组下标", segment))?;
+            if index == items.len() {
+                items.push(toml::Value::Table(toml::Table::new()));
+            }
+            items.get_mut(index)
+                .ok_or_else(|| anyhow::anyhow!("数组下标 {} 超出范围", index))?
+        }
+        _ => return Err(anyhow::anyhow!("{} 不是表格", segment)),
+    };
+    set_path(child, rest, value)
+}
+
+// 把 password_file、key_file 等配置替换为对应文件的内容，文件末尾的换行会被去掉
+fn resolve_secret_files(path: &str, current: &mut toml::Value) -> Result<()> {
+    match current {
+        toml::Value::Table(table) => {
+            for secret in SECRET_KEYS {
+                let file_key = format!("{}_file", secret);
+                let file = match table.remove(&file_key) {
+                    Some(toml::Value::String(file)) => file,
+                    Some(_) => return Err(anyhow::anyhow!("{}{} 必须是文件路径", path, file_key)),
+                    None => continue,
+                };
+                let content = fs::read_to_string(&file)
+                    .map_err(|e| anyhow::anyhow!("{}{}: 读取 {} 失败: {}", path, file_key, file, e))?;
+                table.insert(secret.to_string(), toml::Value::String(content.trim_end_matches(['\r', '\n']).to_string()));
+            }
+            for (key, value) in table.iter_mut() {
+                resolve_secret_files(&format!("{}{}.", path, key), value)?;
+            }
+        }
+        toml::Value::Array(items) => {
+            let parent = path.trim_end_matches('.');
+            for (i, item) in items.iter_mut().enumerate() {
+                resolve_secret_files(&format!("{}[{}].", parent, i), item)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// 配置项的别名，例如[[sources]]中的switch与url
+const KEY_ALIASES: &[&str] = &["switch", "url"];
+
+// 命令行中对配置文件的覆盖，重载配置时同样生效
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub bind: Option<String>,       // host:port
+    pub proxy_file: Option<String>,
+}
+
+impl ConfigOverrides {
+    pub fn apply(&self, config: &mut Config) -> Result<()> {
+        if let Some(bind) = &self.bind {
+            let (host, port) = bind.rsplit_once(':')
+                .ok_or_else(|| anyhow::anyhow!("--bind 格式应为 host:port: {}", bind))?;
+            config.server.bind_host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+            config.server.bind_port = port.parse()
+                .map_err(|_| anyhow::anyhow!("--bind 端口无效: {}", port))?;
+        }
+        if let Some(proxy_file) = &self.proxy_file {
+            config.proxy.proxy_file = proxy_file.clone();
+        }
+        Ok(())
+    }
+}
+
+// 修改后需要重启才能生效的配置项，重载时遇到这些变化会拒绝整个重载
+const RESTART_KEYS: &[&str] = &[
+    "server.bind_host",
+    "server.bind_port",
+    "proxy.proxy_file",
+    "proxy.db_file",
+    "traffic.usage_file",
+    "admin.enabled",
+    "admin.bind_host",
+    "admin.bind_port",
+];
+
+// 监听器的增删与监听地址变化同样需要重启，其余监听器配置在新的连接上生效
+fn requires_restart(key: &str) -> bool {
+    RESTART_KEYS.contains(&key)
+        || key == "listeners"
+        || (key.starts_with("listeners[") && (key.ends_with(".bind_host") || key.ends_with(".bind_port")))
+}
+
+// 运行中的配置，支持热重载
+// 读取方通过get()获取当前配置的快照，需要响应变化的后台任务通过subscribe()等待通知
+#[derive(Clone)]
+pub struct ConfigHandle {
+    path: Option<Arc<PathBuf>>,
+    overrides: Arc<ConfigOverrides>,
+    sender: Arc<watch::Sender<Arc<Config>>>,
+}
+
+impl ConfigHandle {
+    // 不关联配置文件的配置，无法重载
+    pub fn new(config: Config) -> Self {
+        let (sender, _) = watch::channel(Arc::new(config));
+        ConfigHandle {
+            path: None,
+            overrides: Arc::new(ConfigOverrides::default()),
+            sender: Arc::new(sender),
+        }
+    }
+
+    // 从配置文件加载，并记录命令行覆盖以便重载时重新应用
+    pub fn load<P: AsRef<Path>>(path: P, overrides: ConfigOverrides) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut config = Config::read(&path)?;
+        overrides.apply(&mut config)?;
+        config.ensure_valid()?;
+        let (sender, _) = watch::channel(Arc::new(config));
+        Ok(ConfigHandle {
+            path: Some(Arc::new(path)),
+            overrides: Arc::new(overrides),
+            sender: Arc::new(sender),
+        })
+    }
+
+    pub fn get(&self) -> Arc<Config> {
+        self.sender.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Config>> {
+        self.sender.subscribe()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref().map(PathBuf::as_path)
+    }
+
+    // 重新读取配置文件并应用，返回发生变化的配置项
+    // 配置文件解析失败或包含需要重启的变化时保留当前配置并返回错误
+    pub fn reload(&self) -> Result<Vec<String>> {
+        let path = self.path()
+            .ok_or_else(|| anyhow::anyhow!("当前配置未关联配置文件，无法重载"))?;
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("读取配置文件失败: {}", e))?;
+        let (mut config, unknown) = Config::parse(&content)?;
+        warn_unknown_keys(&unknown);
+        self.overrides.apply(&mut config)?;
+        config.ensure_valid()?;
+
+        let changed = changed_keys(&self.get(), &config)?;
+        let restart: Vec<&str> = changed.iter()
+            .map(String::as_str)
+            .filter(|key| requires_restart(key))
+            .collect();
+        if !restart.is_empty() {
+            return Err(anyhow::anyhow!("以下配置需要重启才能生效，已拒绝本次重载: {}", restart.join(", ")));
+        }
+
+        if !changed.is_empty() {
+            self.sender.send_replace(Arc::new(config));
+        }
+        Ok(changed)
+    }
+}
+
+// 比较两份配置，返回发生变化的配置项路径，例如 proxy.auto_switch、sources[0].key
+pub fn changed_keys(old: &Config, new: &Config) -> Result<Vec<String>> {
+    let (old, new) = with_exposed_secrets(|| Ok::<_, serde_json::Error>((serde_json::to_value(old)?, serde_json::to_value(new)?)))?;
+    let mut changed = Vec::new();
+    diff_values("", &old, &new, &mut changed);
+    Ok(changed)
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, changed: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            let mut keys: Vec<&String> = old_map.keys().chain(new_map.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                diff_values(&child, old_map.get(key).unwrap_or(&Value::Null), new_map.get(key).unwrap_or(&Value::Null), changed);
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) if old_items.len() == new_items.len() => {
+            for (i, (old_item, new_item)) in old_items.iter().zip(new_items).enumerate() {
+                diff_values(&format!("{}[{}]", path, i), old_item, new_item, changed);
+            }
+        }
+        _ if old != new => changed.push(path.to_string()),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Config {
+        Config::parse(content).unwrap().0
+    }
+
+    fn issue_keys(content: &str) -> Vec<String> {
+        parse(content).validate().into_iter().map(|issue| issue.key).collect()
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        let (config, unknown) = Config::parse(DEFAULT_CONFIG).unwrap();
+        assert!(unknown.is_empty(), "{:?}", unknown);
+        assert!(config.validate().is_empty(), "{:?}", config.validate());
+        assert!(parse("").validate().is_empty());
+    }
+
+    #[test]
+    fn validates_values() {
+        let cases: &[(&str, &[&str])] = &[
+            ("[server]\nbind_port = 0\nmax_connections = 0", &["server.bind_port", "server.max_connections"]),
+            ("[server]\nbind_host = \" \"", &["server.bind_host"]),
+            ("[proxy]\ntest_timeout = 0\nretry_times = 0\nmax_concurrency = 0",
+                &["proxy.test_timeout", "proxy.retry_times", "proxy.max_concurrency"]),
+            // 关闭健康检查与自动切换时间隔可以为0
+            ("[proxy]\nhealth_check_switch = false\nhealth_check_interval = 0\nauto_switch = false\nswitch_interval = 0", &[]),
+            ("[proxy]\nhealth_check_switch = true\nhealth_check_interval = 0", &["proxy.health_check_interval"]),
+            ("[proxy]\nauto_switch = true\nswitch_interval = 0", &["proxy.switch_interval"]),
+            ("[proxy]\nuse_auth = true\nusername = \"\"", &["proxy.username"]),
+            ("[proxy]\nuse_auth = true\nusername = \"\"\nusers_file = \"users.toml\"", &[]),
+            // 空密码是合法的
+            ("[proxy]\nuse_auth = true\nusername = \"user\"\npassword = \"\"", &[]),
+            ("[proxy]\njudge_url = \"ftp://example.com\"", &["proxy.judge_url"]),
+            ("[proxy]\nimport_files = [\"a.txt\", \" \"]", &["proxy.import_files[1]"]),
+            ("[admin]\nenabled = true\ntoken = \"\"\nbind_port = 0", &["admin.token", "admin.bind_port"]),
+            ("[admin]\nenabled = false\ntoken = \"\"", &[]),
+            ("[access]\nallow = [\"10.0.0.0/8\", \"::1\", \"2001:db8::/32\", \"10.0.0.0/33\", \"localhost\"]",
+                &["access.allow[3]", "access.allow[4]"]),
+            ("[destination]\ndeny = [\"*.example.com\", \"\"]", &["destination.deny[1]"]),
+            ("[[listeners]]\nbind_port = 1080\n[[listeners]]\nbind_port = 1080\nanonymity = \"unknown\"",
+                &["listeners[1].bind_port", "listeners[1].anonymity"]),
+            ("[[listeners]]\nbind_port = 0", &["listeners[0].bind_port"]),
+            ("[[sources]]\ntype = \"fofa\"\nenabled = true\nkey = \"\"\nsize = 0\napi_url = \"not a url\"",
+                &["sources[0].key", "sources[0].size", "sources[0].api_url"]),
+            ("[[sources]]\ntype = \"fofa\"\nenabled = true\nkey = \"k\"\nsize = 10\nquery_str = \"after=\\\"{{today-7天}}\\\"\"",
+                &["sources[0].query_str"]),
+            ("[[sources]]\ntype = \"\"", &["sources[0].type"]),
+            ("[[sources]]\ntype = \"unknown\"\nenabled = true", &["sources[0]"]),
+            // 未启用的代理源不检查
+            ("[[sources]]\ntype = \"fofa\"\nenabled = false\nkey = \"\"", &[]),
+            ("[fofa]\nswitch = true\nfofa_key = \"\"\nsize = 100", &["fofa.fofa_key"]),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(issue_keys(content), *expected, "{}", content);
+        }
+    }
+
+    #[test]
+    fn username_and_password_are_limited_to_255_bytes() {
+        let long = "a".repeat(256);
+        let content = format!("[proxy]\nusername = \"{}\"\npassword = \"{}\"", long, long);
+        assert_eq!(issue_keys(&content), ["proxy.username", "proxy.password"]);
+        let content = format!("[proxy]\nusername = \"{}\"", "a".repeat(255));
+        assert!(issue_keys(&content).is_empty());
+    }
+
+    #[test]
+    fn ensure_valid_lists_every_issue() {
+        let error = parse("[server]\nbind_port = 0\n[proxy]\nretry_times = 0").ensure_valid().unwrap_err().to_string();
+        assert!(error.contains("2个问题"), "{}", error);
+        assert!(error.contains("server.bind_port") && error.contains("proxy.retry_times"), "{}", error);
+    }
+
+    #[test]
+    fn secrets_are_redacted_when_serialized() {
+        let config = parse("[proxy]\npassword = \"hunter2\"\n[[sources]]\ntype = \"fofa\"\nkey = \"fofa-key\"");
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("hunter2") && !json.contains("fofa-key"), "{}", json);
+        assert!(json.contains(r#""password":"******""#), "{}", json);
+        assert!(json.contains(r#""token":"""#), "{}", json);
+
+        let exposed = with_exposed_secrets(|| serde_json::to_string(&config).unwrap());
+        assert!(exposed.contains("hunter2") && exposed.contains("fofa-key"));
+        assert!(!serde_json::to_string(&config).unwrap().contains("hunter2"));
+    }
+
+    #[test]
+    fn detects_secret_changes() {
+        let old = parse("[proxy]\npassword = \"a\"\n[admin]\ntoken = \"t\"");
+        let new = parse("[proxy]\npassword = \"b\"\n[admin]\ntoken = \"t\"");
+        assert_eq!(changed_keys(&old, &new).unwrap(), ["proxy.password"]);
+        assert!(changed_keys(&old, &old).unwrap().is_empty());
+    }
+
+    #[test]
+    fn secret_matches_only_exact_value() {
+        let cases = [("pass", "pass", true), ("pass", "Pass", false), ("pass", "pas", false), ("pass", "passs", false), ("", "", true), ("", "x", false), ("密码", "密码", true)];
+        for (secret, given, expected) in cases {
+            assert_eq!(Secret::from(secret).matches(given), expected, "{:?} {:?}", secret, given);
+        }
+    }
+
+    #[test]
+    fn checks_client_access() {
+        let access = AccessConfig {
+            allow: vec!["10.0.0.0/8".into(), "2001:db8::/32".into(), "192.168.1.1".into()],
+            // deny与allow重叠时deny优先
+            deny: vec!["10.1.0.0/16".into()],
+            no_auth: vec!["10.2.0.0/16".into(), "2001:db8:1::/48".into()],
+            log_rejected: true,
+        };
+        let cases = [
+            ("10.0.0.1", ClientAccess::Allowed),
+            ("10.1.2.3", ClientAccess::Denied),
+            ("10.2.2.3", ClientAccess::NoAuth),
+            ("192.168.1.1", ClientAccess::Allowed),
+            ("192.168.1.2", ClientAccess::Denied),
+            ("8.8.8.8", ClientAccess::Denied),
+            ("2001:db8::1", ClientAccess::Allowed),
+            ("2001:db8:1::1", ClientAccess::NoAuth),
+            ("2001:db9::1", ClientAccess::Denied),
+            // IPv6监听器上的IPv4客户端
+            ("::ffff:10.0.0.1", ClientAccess::Allowed),
+            ("::ffff:10.1.0.1", ClientAccess::Denied),
+        ];
+        for (ip, expected) in cases {
+            assert_eq!(access.check(ip.parse().unwrap()), expected, "{}", ip);
+        }
+
+        let open = AccessConfig { deny: vec!["::1".into()], ..AccessConfig::default() };
+        assert_eq!(open.check("8.8.8.8".parse().unwrap()), ClientAccess::Allowed);
+        assert_eq!(open.check("::1".parse().unwrap()), ClientAccess::Denied);
+    }
+
+    #[test]
+    fn blocks_private_destinations() {
+        let blocked = ["10.0.0.1", "172.16.0.1", "192.168.0.1", "127.0.0.1", "169.254.169.254", "0.0.0.0",
+            "255.255.255.255", "::1", "[::1]", "::", "fc00::1", "fd12::1", "fe80::1", "::ffff:127.0.0.1",
+            "localhost", "LOCALHOST.", "foo.localhost",
+            // inet_aton的数字写法
+            "127.1", "2130706433", "0x7f000001", "0X7F.0.0.1", "0177.0.0.1", "10.1", "0.0.0.5", "0xa.0xa0000",
+            "127.0.0.1.", "99999999999", "08.0.0.1", "1.2.3.4.5",
+            // 本网络与运营商级NAT(云服务器元数据)
+            "0.1.2.3", "100.64.0.1", "100.100.100.200", "100.127.255.255"];
+        let allowed = ["8.8.8.8", "172.32.0.1", "2001:4860::8888", "example.com", "localhost.example.com", "fec0::1",
+            "100.63.255.255", "100.128.0.1", "134744072", "0x08080808", "1e100.net", "0xdead.example.com", "123.example"];
+
+        let config = DestinationConfig::default();
+        for host in blocked {
+            assert!(!config.allows(host, 80), "{}", host);
+        }
+        for host in allowed {
+            assert!(config.allows(host, 80), "{}", host);
+        }
+
+        let disabled = DestinationConfig { block_private: false, ..DestinationConfig::default() };
+        for host in blocked.iter().chain(&allowed) {
+            assert!(disabled.allows(host, 80), "{}", host);
+        }
+    }
+
+    #[test]
+    fn parses_numeric_ipv4_forms() {
+        let cases = [
+            ("127.1", Some("127.0.0.1")), ("2130706433", Some("127.0.0.1")), ("0x7f000001", Some("127.0.0.1")),
+            ("0177.0.0.1", Some("127.0.0.1")), ("10.1", Some("10.0.0.1")), ("192.168.257", Some("192.168.1.1")),
+            ("0", Some("0.0.0.0")), ("1.2.3.4", Some("1.2.3.4")),
+            ("256.0.0.1", None), ("1.2.3.256", None), ("1.16777216", None), ("4294967296", None), ("08", None),
+            ("1.2.3.4.5", None), ("", None), ("1..2", None), ("example.com", None),
+        ];
+        for (host, expected) in cases {
+            assert_eq!(parse_inet_aton(host).map(|ip| ip.to_string()).as_deref(), expected, "{}", host);
+        }
+    }
+
+    #[test]
+    fn destination_allow_overrides_deny_and_private_block() {
+        let config = DestinationConfig {
+            block_private: true,
+            deny: vec!["*:25".into(), "*.example.com".into(), "8.8.0.0/16".into()],
+            allow: vec!["10.0.0.5".into(), "mail.example.com:25".into(), "[fd00::1]:443".into()],
+        };
+        let cases = [
+            ("10.0.0.5", 80, true),
+            ("10.0.0.6", 80, false),
+            ("mail.example.com", 25, true),
+            ("mail.example.com", 587, false),
+            ("smtp.gmail.com", 25, false),
+            ("www.example.com", 443, false),
+            ("example.com", 443, false),
+            ("8.8.8.8", 53, false),
+            ("134744072", 53, false),
+            ("8.8.2056", 53, false),
+            ("1.1.1.1", 53, true),
+            ("0xa.0.0.5", 80, true),
+            ("fd00::1", 443, true),
+            ("[fd00::1]", 443, true),
+            ("fd00::1", 80, false),
+        ];
+        for (host, port, expected) in cases {
+            assert_eq!(config.allows(host, port), expected, "{}:{}", host, port);
+        }
+    }
+
+    #[test]
+    fn reports_unknown_keys() {
+        let (_, unknown) = Config::parse("[proxy]\nretry_time = 3\n[[sources]]\ntype = \"url\"\nswitch = true\nurls = []").unwrap();
+        assert_eq!(unknown, ["proxy.retry_time", "sources[0].urls"]);
+    }
+}
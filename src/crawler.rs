@@ -1,261 +1,605 @@
-use crate::config::Config;
+use crate::config::{Config, SourceConfig};
+use crate::fsutil::write_atomic;
+use crate::proxy_pool::{format_proxy_line, normalize_address, parse_proxy_line, Credentials, ProxyEntry, ProxyPool, ProxyProtocol};
+use crate::store::{unix_now, Provenance, SourceYield};
 use anyhow::Result;
-use base64::{Engine as _, engine::general_purpose};
-use serde::Deserialize;
-use serde_json;
-use std::fs::OpenOptions;
-use std::io::Write;
+use async_trait::async_trait;
 use colored::*;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-// FOFA API响应结构
-#[derive(Debug, Deserialize)]
-pub struct FofaResponse {
-    pub error: bool,
-    pub results: Vec<Vec<String>>,
+pub mod censys;
+pub mod fofa;
+pub mod hunter;
+mod paginate;
+pub mod quake;
+pub mod request;
+pub mod shodan;
+pub mod template;
+#[cfg(test)]
+mod testutil;
+pub mod url_list;
+pub mod zoomeye;
+
+// 代理源获取到的候选代理
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub address: String,
+    pub source: String,     // 来源名称
+    pub query: String,      // 获取时使用的查询语句
+    pub fetched_at: u64,    // 获取时间(unix秒)
+    pub protocol: ProxyProtocol,
+    pub credentials: Option<Credentials>,
+    pub tags: Vec<String>,
+}
+
+impl Candidate {
+    pub fn new(address: String, source: &str, query: &str) -> Self {
+        Candidate {
+            address,
+            source: source.to_string(),
+            query: query.to_string(),
+            fetched_at: unix_now(),
+            protocol: ProxyProtocol::Socks5,
+            credentials: None,
+            tags: Vec::new(),
+        }
+    }
+
+    // 由导入的代理条目生成候选代理，保留协议、认证信息与标签
+    pub fn from_entry(entry: ProxyEntry, source: &str, query: &str) -> Self {
+        Candidate {
+            protocol: entry.protocol,
+            credentials: entry.credentials,
+            tags: entry.tags,
+            ..Candidate::new(entry.address, source, query)
+        }
+    }
+
+    pub fn provenance(&self) -> Provenance {
+        Provenance {
+            source: self.source.clone(),
+            query: self.query.clone(),
+            fetched_at: self.fetched_at,
+        }
+    }
+
+    pub fn into_entry(self) -> ProxyEntry {
+        let provenance = self.provenance();
+        ProxyEntry {
+            protocol: self.protocol,
+            credentials: self.credentials,
+            tags: self.tags,
+            ..ProxyEntry::new(self.address, Some(provenance))
+        }
+    }
 }
 
-// Quake API响应结构
-#[derive(Debug, Deserialize)]
-pub struct QuakeResponse {
-    pub code: i32,
-    pub message: String,
-    pub data: Vec<QuakeItem>,
+// 代理源接口，新增代理源只需实现该trait并在SourceRegistry中注册
+#[async_trait]
+pub trait ProxySource: Send + Sync {
+    fn name(&self) -> &str;
+    async fn fetch(&self) -> Result<Vec<Candidate>>;
+
+    // 本次爬取预计消耗的配额，单位与该API的计费单位一致，None表示不消耗配额
+    fn estimated_cost(&self) -> Option<u64> {
+        None
+    }
+
+    // 单次爬取允许消耗的配额预算，0表示不限制
+    fn quota_budget(&self) -> u64 {
+        0
+    }
+
+    // 爬取前查询剩余配额，None表示该API不支持查询
+    async fn remaining_quota(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    // 最近一次请求响应中携带的剩余配额
+    fn reported_quota(&self) -> Option<u64> {
+        None
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct QuakeItem {
-    pub ip: String,
-    pub port: u32,
+// 单个代理源的获取统计
+#[derive(Debug, Clone)]
+pub struct SourceStats {
+    pub name: String,
+    pub fetched: usize,
+    pub elapsed: Duration,
+    pub error: Option<String>,
+    pub quota_remaining: Option<u64>,
 }
 
-// Hunter API响应结构
-#[derive(Debug, Deserialize)]
-pub struct HunterResponse {
-    pub code: u32,
-    pub message: String,
-    pub data: HunterData,
+// 爬取前检查配额，预计消耗超过预算或剩余配额时拒绝爬取，返回剩余配额
+async fn check_quota(source: &dyn ProxySource) -> Result<Option<u64>> {
+    let cost = match source.estimated_cost() {
+        Some(cost) => cost,
+        None => return Ok(None),
+    };
+
+    let budget = source.quota_budget();
+    if budget > 0 && cost > budget {
+        return Err(anyhow::anyhow!("预计消耗配额{}超过预算{}，已拒绝爬取", cost, budget));
+    }
+
+    let remaining = match source.remaining_quota().await {
+        Ok(remaining) => remaining,
+        Err(e) => {
+            eprintln!("{} {}", format!("查询{}剩余配额失败:", source.name()).yellow().bold(), e);
+            None
+        }
+    };
+    if let Some(remaining) = remaining {
+        if cost > remaining {
+            return Err(anyhow::anyhow!("预计消耗配额{}超过剩余配额{}，已拒绝爬取", cost, remaining));
+        }
+    }
+    Ok(remaining)
 }
 
-#[derive(Debug, Deserialize)]
-pub struct HunterData {
-    pub total: u64,
-    pub arr: Vec<HunterItem>,
+// 判断API错误信息是否表示配额/积分耗尽
+pub(crate) fn is_quota_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ["余额", "积分", "配额", "f点", "quota", "credit", "820031", "q3005"]
+        .iter()
+        .any(|keyword| message.contains(keyword))
 }
 
-#[derive(Debug, Deserialize)]
-pub struct HunterItem {
-    pub ip: String,
-    pub port: u32,
+pub type SourceBuilder = fn(&SourceConfig) -> Result<Box<dyn ProxySource>>;
+
+// 代理源注册表，根据配置中的type字段构造对应的代理源
+pub struct SourceRegistry {
+    builders: HashMap<String, SourceBuilder>,
 }
 
-pub async fn fetch_proxies(config: &Config) -> Result<()> {
-    let mut proxies = Vec::new();
-    let mut fetch_success = false;
-    
-    // 从FOFA获取代理
-    if config.fofa.switch {
-        match fetch_from_fofa(config).await {
-            Ok(fofa_proxies) => {
-                println!("{} {}", "从FOFA获取代理成功:".green().bold(), fofa_proxies.len().to_string().yellow().bold());
-                proxies.extend(fofa_proxies);
-                fetch_success = true;
-            },
-            Err(e) => {
-                eprintln!("{} {}", "从FOFA获取代理失败:".red().bold(), e);
-            }
+impl Default for SourceRegistry {
+    fn default() -> Self {
+        let mut registry = SourceRegistry::new();
+        registry.register("fofa", fofa::FofaSource::build);
+        registry.register("quake", quake::QuakeSource::build);
+        registry.register("hunter", hunter::HunterSource::build);
+        registry.register("url", url_list::UrlListSource::build);
+        registry.register("shodan", shodan::ShodanSource::build);
+        registry.register("zoomeye", zoomeye::ZoomEyeSource::build);
+        registry.register("censys", censys::CensysSource::build);
+        registry
+    }
+}
+
+impl SourceRegistry {
+    // 空注册表，不含内置代理源
+    pub fn new() -> Self {
+        SourceRegistry {
+            builders: HashMap::new(),
         }
     }
 
-    // 从Quake获取代理
-    if config.quake.switch {
-        match fetch_from_quake(config).await {
-            Ok(quake_proxies) => {
-                println!("{} {}", "从Quake获取代理成功:".green().bold(), quake_proxies.len().to_string().yellow().bold());
-                proxies.extend(quake_proxies);
-                fetch_success = true;
-            },
-            Err(e) => {
-                eprintln!("{} {}", "从Quake获取代理失败:".red().bold(), e);
+    pub fn register(&mut self, kind: &str, builder: SourceBuilder) {
+        self.builders.insert(kind.to_lowercase(), builder);
+    }
+
+    pub fn build(&self, config: &SourceConfig) -> Result<Box<dyn ProxySource>> {
+        match self.builders.get(&config.kind.to_lowercase()) {
+            Some(builder) => builder(config),
+            None => Err(anyhow::anyhow!("未知的代理源类型: {}", config.kind)),
+        }
+    }
+
+    // 构造配置中所有已启用的代理源
+    pub fn build_enabled(&self, config: &Config) -> Vec<Box<dyn ProxySource>> {
+        let mut sources = Vec::new();
+        for source_config in config.source_configs().iter().filter(|s| s.enabled) {
+            match self.build(source_config) {
+                Ok(source) => sources.push(source),
+                Err(e) => eprintln!("{} {} - {}", "创建代理源失败:".red().bold(), source_config.display_name(), e),
             }
         }
+        sources
     }
-    
-    // 从Hunter获取代理
-    if config.hunter.switch {
-        match fetch_from_hunter(config).await {
-            Ok(hunter_proxies) => {
-                println!("{} {}", "从Hunter获取代理成功:".green().bold(), hunter_proxies.len().to_string().yellow().bold());
-                proxies.extend(hunter_proxies);
-                fetch_success = true;
-            },
+}
+
+// 依次从各代理源获取候选代理，返回去重后的候选代理和各源的统计
+pub async fn crawl(sources: &[Box<dyn ProxySource>]) -> (Vec<Candidate>, Vec<SourceStats>) {
+    let mut candidates: Vec<Candidate> = Vec::new();
+    let mut stats = Vec::with_capacity(sources.len());
+
+    for source in sources {
+        let start = Instant::now();
+        let quota = match check_quota(source.as_ref()).await {
+            Ok(quota) => quota,
+            Err(e) => {
+                eprintln!("{} {}", format!("{}配额检查未通过:", source.name()).red().bold(), e);
+                stats.push(SourceStats {
+                    name: source.name().to_string(),
+                    fetched: 0,
+                    elapsed: start.elapsed(),
+                    error: Some(e.to_string()),
+                    quota_remaining: None,
+                });
+                continue;
+            }
+        };
+
+        let result = source.fetch().await;
+        let quota_remaining = source.reported_quota().or(quota);
+        match result {
+            Ok(fetched) => {
+                println!("{} {} {}",
+                    format!("从{}获取代理成功:", source.name()).green().bold(),
+                    fetched.len().to_string().yellow().bold(),
+                    "个".green().bold()
+                );
+                stats.push(SourceStats {
+                    name: source.name().to_string(),
+                    fetched: fetched.len(),
+                    elapsed: start.elapsed(),
+                    error: None,
+                    quota_remaining,
+                });
+                candidates.extend(fetched);
+            }
             Err(e) => {
-                eprintln!("{} {}", "从Hunter获取代理失败:".red().bold(), e);
+                eprintln!("{} {}", format!("从{}获取代理失败:", source.name()).red().bold(), e);
+                stats.push(SourceStats {
+                    name: source.name().to_string(),
+                    fetched: 0,
+                    elapsed: start.elapsed(),
+                    error: Some(e.to_string()),
+                    quota_remaining,
+                });
             }
         }
     }
-    
-    if !fetch_success {
-        return Err(anyhow::anyhow!("所有代理源获取失败"));
+
+    // 去重，保留最先获取到的来源
+    let mut seen = HashSet::new();
+    candidates.retain(|c| seen.insert(c.address.clone()));
+
+    (candidates, stats)
+}
+
+pub fn print_source_stats(stats: &[SourceStats]) {
+    println!("\n{}", "代理源统计:".cyan().bold());
+    for stat in stats {
+        match &stat.error {
+            None => println!("  {:<12} {} {} ({}ms){}",
+                stat.name.cyan(),
+                "获取".green(),
+                stat.fetched.to_string().yellow().bold(),
+                stat.elapsed.as_millis(),
+                stat.quota_remaining
+                    .map(|quota| format!(" 剩余配额: {}", quota))
+                    .unwrap_or_default()
+            ),
+            Some(e) => println!("  {:<12} {} {}",
+                stat.name.cyan(),
+                "失败:".red(),
+                e
+            ),
+        }
+    }
+    println!();
+}
+
+// 输出各代理源的产出: 获取数 / 检测可用数 / 获取24小时后仍可用数
+pub fn print_source_yield(yields: &[SourceYield]) {
+    println!("\n{}", "代理源产出:".cyan().bold());
+    if yields.is_empty() {
+        println!("  {}", "暂无记录".yellow());
+    }
+    for stat in yields {
+        let name = if stat.source.is_empty() { "未知来源" } else { stat.source.as_str() };
+        let rate = if stat.fetched == 0 { 0.0 } else { stat.alive as f64 * 100.0 / stat.fetched as f64 };
+        println!("  {:<12} {} {} {} {} {} {} ({:.1}%)",
+            name.cyan(),
+            "获取".green(),
+            stat.fetched.to_string().yellow().bold(),
+            "可用".green(),
+            stat.alive.to_string().yellow().bold(),
+            "24小时后仍可用".green(),
+            stat.alive_after_24h.to_string().yellow().bold(),
+            rate
+        );
     }
-    
-    // 去重
-    proxies.sort();
-    proxies.dedup();
-    
-    // 写入文件
-    let mut file = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(&config.proxy.proxy_file)
-        .map_err(|e| anyhow::anyhow!("打开代理文件失败: {}", e))?;
-    
-    for proxy in &proxies {
-        writeln!(file, "{}", proxy)
+    println!();
+}
+
+// 合并爬取结果到代理文件的统计
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    pub existing: usize,      // 文件中已有的代理数
+    pub added: Vec<String>,   // 新增的代理地址
+    pub duplicates: usize,    // 已存在而跳过的候选代理数
+    pub invalid: usize,       // 地址无效的候选代理数
+}
+
+// 将候选代理合并到代理文件，地址规范化后去重
+// 文件中已有的行(包括手动添加的代理和注释)原样保留，新代理按地址排序追加在末尾；dry_run时不写入文件
+pub fn merge_into_file<P: AsRef<Path>>(path: P, candidates: Vec<Candidate>, dry_run: bool) -> Result<MergeReport> {
+    let path = path.as_ref();
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(anyhow::anyhow!("读取代理文件失败: {}", e)),
+    };
+
+    let mut report = MergeReport::default();
+    let mut known = HashSet::new();
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    for line in &lines {
+        if let Some(entry) = parse_proxy_line(line) {
+            report.existing += 1;
+            known.insert(normalize_address(&entry.address).unwrap_or(entry.address));
+        }
+    }
+
+    let mut new_entries = Vec::new();
+    for mut candidate in candidates {
+        let address = match normalize_address(&candidate.address) {
+            Some(address) => address,
+            None => {
+                report.invalid += 1;
+                continue;
+            }
+        };
+        if !known.insert(address.clone()) {
+            report.duplicates += 1;
+            continue;
+        }
+        candidate.address = address;
+        new_entries.push(candidate.into_entry());
+    }
+    new_entries.sort_by(|a, b| a.address.cmp(&b.address));
+    report.added = new_entries.iter().map(|e| e.address.clone()).collect();
+
+    if !dry_run && !new_entries.is_empty() {
+        // 原子写入文件，新代理附带来源信息
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+        lines.extend(new_entries.iter().map(format_proxy_line));
+        let mut content = lines.join("\n");
+        content.push('\n');
+        write_atomic(path, content.as_bytes())
             .map_err(|e| anyhow::anyhow!("写入代理文件失败: {}", e))?;
     }
-    
-    println!("{} {}", "共获取并保存代理:".green().bold(), proxies.len().to_string().yellow().bold());
-    Ok(())
+    Ok(report)
 }
 
-async fn fetch_from_fofa(config: &Config) -> Result<Vec<String>> {
-    println!("{}", "从FOFA API获取代理列表...".cyan().bold());
-
-    let query_base64 = general_purpose::STANDARD.encode(&config.fofa.query_str);
-    
-    let url = format!(
-        "{}?key={}&qbase64={}&size={}",
-        config.fofa.api_url,
-        config.fofa.fofa_key,
-        query_base64,
-        config.fofa.size
+// dry_run时最多列出的新增代理数
+const DRY_RUN_LIST_LIMIT: usize = 50;
+
+pub fn print_merge_report<P: AsRef<Path>>(path: P, report: &MergeReport, dry_run: bool) {
+    let path = path.as_ref().display().to_string();
+    if dry_run {
+        println!("{} {}", "[dry-run] 以下变化不会写入:".yellow().bold(), path);
+    }
+    println!("{} {} {} {} {} {} {} {}",
+        "已有代理:".green().bold(),
+        report.existing.to_string().yellow().bold(),
+        "新增:".green().bold(),
+        report.added.len().to_string().yellow().bold(),
+        "重复:".green().bold(),
+        report.duplicates.to_string().yellow(),
+        "无效:".green().bold(),
+        report.invalid.to_string().yellow()
     );
+    if dry_run {
+        for address in report.added.iter().take(DRY_RUN_LIST_LIMIT) {
+            println!("  {} {}", "+".green().bold(), address.cyan());
+        }
+        if report.added.len() > DRY_RUN_LIST_LIMIT {
+            println!("  {}", format!("... 另有{}个", report.added.len() - DRY_RUN_LIST_LIMIT).bright_black());
+        }
+    } else if !report.added.is_empty() {
+        println!("{} {}", "代理已保存到:".green().bold(), path);
+    }
+}
+
+// 爬取结果写入的文件: 配置了暂存文件时写入暂存文件，否则合并到代理文件
+pub fn crawl_output_file(config: &Config) -> &str {
+    if config.crawler.staging_file.is_empty() {
+        &config.proxy.proxy_file
+    } else {
+        &config.crawler.staging_file
+    }
+}
+
+// 传入运行中的代理池时在代理池的文件锁下写入，避免与其他写入者交错
+pub async fn fetch_proxies(config: &Config, pool: Option<&ProxyPool>) -> Result<Vec<SourceStats>> {
+    let sources = SourceRegistry::default().build_enabled(config);
+    if sources.is_empty() {
+        return Err(anyhow::anyhow!("没有可用的代理源"));
+    }
+
+    let (candidates, stats) = crawl(&sources).await;
+    print_source_stats(&stats);
+
+    if stats.iter().all(|s| s.error.is_some()) {
+        return Err(anyhow::anyhow!("所有代理源获取失败"));
+    }
 
-    let client = reqwest::Client::new();
-    let response = client.get(&url)
-        .send()
-        .await
-        .map_err(|e| anyhow::anyhow!("发送FOFA API请求失败: {}", e))?;
-    
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!("FOFA API请求失败: HTTP状态码 {}", response.status()));
-    }
-    
-    let fofa_data: FofaResponse = response
-        .json()
-        .await
-        .map_err(|e| anyhow::anyhow!("解析FOFA API响应失败: {}", e))?;
-    
-    if fofa_data.error {
-        return Err(anyhow::anyhow!("FOFA API返回错误"));
-    }
-
-    let mut proxies = Vec::new();
-    for proxy in fofa_data.results {
-        if proxy.len() >= 1 {
-            proxies.push(proxy[0].clone());
+    let output = crawl_output_file(config);
+    let _guard = match pool {
+        Some(pool) => Some(pool.lock_file().await),
+        None => None,
+    };
+    let report = merge_into_file(output, candidates, config.crawler.dry_run)?;
+    print_merge_report(output, &report, config.crawler.dry_run);
+    Ok(stats)
+}
+
+// 处理后台爬取到的候选代理，返回新增数量
+// 配置了暂存文件或dry_run时只写入暂存文件或输出变化，不合并到运行中的代理池
+async fn apply_candidates(pool: &ProxyPool, candidates: Vec<Candidate>) -> usize {
+    let config = pool.get_config();
+    if !config.crawler.dry_run && config.crawler.staging_file.is_empty() {
+        return merge_candidates(pool, candidates).await;
+    }
+
+    let output = crawl_output_file(&config);
+    let _guard = pool.lock_file().await;
+    match merge_into_file(output, candidates, config.crawler.dry_run) {
+        Ok(report) => {
+            print_merge_report(output, &report, config.crawler.dry_run);
+            if config.crawler.dry_run { 0 } else { report.added.len() }
+        }
+        Err(e) => {
+            eprintln!("{} {}", "写入爬取结果失败:".red().bold(), e);
+            0
         }
     }
+}
 
-    Ok(proxies)
+// 立即从所有已启用的代理源爬取，处理方式与后台爬取相同，返回获取数与新增数
+pub async fn crawl_enabled(pool: &ProxyPool) -> Result<(usize, usize)> {
+    let sources = SourceRegistry::default().build_enabled(&pool.get_config());
+    if sources.is_empty() {
+        return Err(anyhow::anyhow!("没有可用的代理源"));
+    }
+    let (candidates, stats) = crawl(&sources).await;
+    print_source_stats(&stats);
+    let fetched = candidates.len();
+    Ok((fetched, apply_candidates(pool, candidates).await))
 }
 
-async fn fetch_from_quake(config: &Config) -> Result<Vec<String>> {
-    println!("{}", "从Quake API获取代理列表...".cyan().bold());
-
-    let url = &config.quake.api_url;
-    let client = reqwest::Client::new();
-    
-    // 准备请求体
-    let request_body = serde_json::json!({
-        "query": config.quake.query_str,
-        "latest": "True",
-        "start": 0,
-        "size": config.quake.size,
-        "include": ["ip", "port"]
+// 测试候选代理中需要测试的部分，并把可用的合并到正在运行的代理池，返回新增数量
+// 地址规范化后去重，代理池和隔离区中已有的地址不会重复测试；数据库中检测失败的代理超过RETEST_AFTER_SECS后重新测试
+pub async fn merge_candidates(pool: &ProxyPool, candidates: Vec<Candidate>) -> usize {
+    let mut unseen = Vec::new();
+    let mut queued = HashSet::new();
+    for mut candidate in candidates {
+        candidate.address = match normalize_address(&candidate.address) {
+            Some(address) => address,
+            None => continue,
+        };
+        // 多个代理源可能返回同一地址，只测试一次
+        if !queued.insert(candidate.address.clone()) {
+            continue;
+        }
+        if !pool.contains_any(&candidate.address).await
+            && !pool.get_store().checked_within(&candidate.address, RETEST_AFTER_SECS).await
+        {
+            unseen.push(candidate);
+        }
+    }
+    if unseen.is_empty() {
+        return 0;
+    }
+
+    let valid = pool.test_proxies(
+        unseen,
+        "新代理测试",
+        pool.get_config().proxy.test_timeout,
+        false,
+        false,
+        |candidate: Candidate| (candidate.address.clone(), Some(candidate.into_entry()))
+    ).await;
+
+    let added = pool.merge_proxies(valid).await;
+    if added > 0 {
+        if let Err(e) = pool.persist().await {
+            eprintln!("{} {}", "更新代理文件失败:".red().bold(), e);
+        }
+    }
+    added
+}
+
+// 爬取到数据库中已有的代理时，距上次检测超过该时间才重新测试
+const RETEST_AFTER_SECS: u64 = 6 * 60 * 60;
+
+// 启动后台定时爬取，每个配置了crawl_interval的代理源独立调度，返回启动的任务数
+// 重载配置后代理源配置有变化时，按新配置重建所有定时爬取任务
+pub fn start_scheduled_crawl(pool: Arc<ProxyPool>) -> usize {
+    let mut tasks = spawn_scheduled_crawls(&pool);
+    let started = tasks.len();
+
+    let mut changes = pool.config_handle().subscribe();
+    let mut current = pool.get_config().source_configs();
+    tokio::spawn(async move {
+        while changes.changed().await.is_ok() {
+            let sources = pool.get_config().source_configs();
+            if sources == current {
+                continue;
+            }
+            current = sources;
+            for task in tasks.drain(..) {
+                task.abort();
+            }
+            tasks = spawn_scheduled_crawls(&pool);
+            println!("{} {}",
+                "代理源配置已变化，定时爬取任务已重建，代理源数:".green().bold(),
+                tasks.len().to_string().yellow().bold()
+            );
+        }
     });
-    
-    let response = client.post(url)
-        .header("Content-Type", "application/json")
-        .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/83.0.4103.116 Safari/537.36")
-        .header("X-QuakeToken", &config.quake.quake_key)
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| anyhow::anyhow!("发送Quake API请求失败: {}", e))?;
-    
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!("Quake API请求失败: HTTP状态码 {}", response.status()));
-    }
-    
-    let quake_data: QuakeResponse = response
-        .json()
-        .await
-        .map_err(|e| anyhow::anyhow!("解析Quake API响应失败: {}", e))?;
-    
-    if quake_data.code != 0 {
-        return Err(anyhow::anyhow!("Quake API返回错误: {}", quake_data.message));
-    }
-
-    let mut proxies = Vec::new();
-    for item in quake_data.data {
-        let proxy = format!("{}:{}", item.ip, item.port);
-        proxies.push(proxy);
-    }
-
-    Ok(proxies)
+
+    started
 }
 
-async fn fetch_from_hunter(config: &Config) -> Result<Vec<String>> {
-    println!("{}", "从Hunter API获取代理列表...".cyan().bold());
-
-    let query_base64 = general_purpose::STANDARD.encode(&config.hunter.query_str);
-    let mut all_proxies = Vec::new();
-    
-    // 遍历所有页
-    for page in 1..=config.hunter.size {
-        let url = format!(
-            "{}?api-key={}&search={}&page={}&page_size=100",
-            config.hunter.api_url,
-            config.hunter.hunter_key,
-            query_base64,
-            page
-        );
-        
-        let client = reqwest::Client::new();
-        let response = client.get(&url)
-            .send()
-            .await
-            .map_err(|e| anyhow::anyhow!("发送Hunter API请求失败 (第{}页): {}", page, e))?;
-        
-        if !response.status().is_success() {
-            eprintln!("{} {}", format!("Hunter API请求第{}页失败: HTTP状态码", page).red().bold(), response.status());
-            continue; // 继续下一页而不是完全中止
+fn spawn_scheduled_crawls(pool: &Arc<ProxyPool>) -> Vec<tokio::task::JoinHandle<()>> {
+    let registry = SourceRegistry::default();
+    let mut tasks = Vec::new();
+
+    for source_config in pool.get_config().source_configs() {
+        if !source_config.enabled || source_config.crawl_interval == 0 {
+            continue;
         }
-        
-        let hunter_data: HunterResponse = match response.json().await {
-            Ok(data) => data,
+        let source = match registry.build(&source_config) {
+            Ok(source) => source,
             Err(e) => {
-                eprintln!("{} {}", format!("解析Hunter API响应失败 (第{}页):", page).red().bold(), e);
-                continue; // 继续下一页
+                eprintln!("{} {} - {}", "创建代理源失败:".red().bold(), source_config.display_name(), e);
+                continue;
             }
         };
-        
-        if hunter_data.code != 200 {
-            eprintln!("{} {}", format!("Hunter API返回错误 (第{}页):", page).red().bold(), hunter_data.message);
-            continue; // 继续下一页
-        }
-        
-        // 提取代理
-        for item in hunter_data.data.arr {
-            let proxy = format!("{}:{}", item.ip, item.port);
-            all_proxies.push(proxy);
-        }
-        
-        // 添加延迟，防止API限流
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+
+        let pool = Arc::clone(pool);
+        let interval = Duration::from_secs(source_config.crawl_interval);
+        tasks.push(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+
+                let (candidates, _) = crawl(std::slice::from_ref(&source)).await;
+                let fetched = candidates.len();
+                let added = apply_candidates(&pool, candidates).await;
+                println!("{} {} {} {} {}",
+                    format!("[定时爬取] {}", source.name()).blue().bold(),
+                    "获取:".green(),
+                    fetched.to_string().yellow().bold(),
+                    "新增可用:".green(),
+                    added.to_string().yellow().bold()
+                );
+            }
+        }));
     }
-    
-    Ok(all_proxies)
-} 
\ No newline at end of file
+
+    tasks
+}
+
+// 启动补充任务：代理池低于水位线时，立即按当前配置从所有已启用的代理源爬取并合并新代理
+pub fn start_refill_listener(pool: Arc<ProxyPool>) {
+    tokio::spawn(async move {
+        loop {
+            pool.refill_requested().await;
+
+            let sources = SourceRegistry::default().build_enabled(&pool.get_config());
+            if sources.is_empty() {
+                println!("{}", "[补充代理] 未启用任何代理源，只能复测隔离区".yellow().bold());
+                continue;
+            }
+            println!("{}", "[补充代理] 开始从已启用的代理源爬取...".blue().bold());
+            let (candidates, _) = crawl(&sources).await;
+            let added = apply_candidates(&pool, candidates).await;
+            println!("{} {}",
+                "[补充代理] 新增代理:".blue().bold(),
+                added.to_string().yellow().bold()
+            );
+        }
+    });
+}
@@ -0,0 +1,267 @@
+use super::paginate::{Page, PageRequest, Paginator};
+use super::request::{send_with_retry, RateLimiter};
+use super::template;
+use super::{Candidate, ProxySource};
+use crate::config::SourceConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+use colored::*;
+use serde::Deserialize;
+
+pub const DEFAULT_API_URL: &str = "https://search.censys.io/api/v2/hosts/search";
+
+const PAGE_SIZE: u64 = 100;
+// Censys免费账号的请求频率较低，请求间隔适当放大
+const DEFAULT_INTERVAL_MS: u64 = 2500;
+
+// Censys API响应结构
+#[derive(Debug, Deserialize)]
+pub struct CensysResponse {
+    pub code: u32,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+    pub result: Option<CensysResult>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CensysResult {
+    #[serde(default)]
+    pub total: u64,
+    #[serde(default)]
+    pub hits: Vec<CensysHit>,
+    #[serde(default)]
+    pub links: CensysLinks,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CensysLinks {
+    #[serde(default)]
+    pub next: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CensysHit {
+    pub ip: String,
+    #[serde(default)]
+    pub services: Vec<CensysService>,
+    #[serde(default)]
+    pub matched_services: Vec<CensysService>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CensysService {
+    pub port: u16,
+    #[serde(default)]
+    pub service_name: String,
+}
+
+// 解析Censys一页结果，返回代理地址与下一页游标(为空表示没有下一页)
+// 优先使用命中查询条件的服务，否则选取服务名为SOCKS的端口
+pub fn parse_response(body: &str) -> Result<(Vec<String>, String)> {
+    let data: CensysResponse = serde_json::from_str(body)
+        .map_err(|e| anyhow::anyhow!("解析Censys API响应失败: {}", e))?;
+
+    let result = match data.result {
+        Some(result) if data.code == 200 => result,
+        _ => {
+            let message = data.error.unwrap_or(data.status);
+            return Err(anyhow::anyhow!("Censys API返回错误({}): {}", data.code, message));
+        }
+    };
+
+    let mut proxies = Vec::new();
+    for hit in result.hits {
+        let services = if hit.matched_services.is_empty() {
+            hit.services.iter()
+                .filter(|s| s.service_name.to_ascii_uppercase().contains("SOCKS"))
+                .collect::<Vec<_>>()
+        } else {
+            hit.matched_services.iter().collect()
+        };
+        for service in services {
+            proxies.push(format!("{}:{}", hit.ip, service.port));
+        }
+    }
+    Ok((proxies, result.links.next))
+}
+
+// Censys账户信息响应结构
+#[derive(Debug, Deserialize)]
+pub struct CensysAccountResponse {
+    #[serde(default)]
+    pub quota: CensysQuota,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CensysQuota {
+    #[serde(default)]
+    pub used: u64,
+    #[serde(default)]
+    pub allowance: u64,
+}
+
+pub struct CensysSource {
+    config: SourceConfig,
+    api_id: String,
+    api_secret: String,
+    limiter: RateLimiter,
+}
+
+impl CensysSource {
+    // key格式为 API_ID:API_SECRET
+    pub fn build(config: &SourceConfig) -> Result<Box<dyn ProxySource>> {
+        let (api_id, api_secret) = config.key.expose().split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Censys的key格式应为 API_ID:API_SECRET"))?;
+        Ok(Box::new(CensysSource {
+            config: config.clone(),
+            api_id: api_id.to_string(),
+            api_secret: api_secret.to_string(),
+            limiter: RateLimiter::for_source(config, DEFAULT_INTERVAL_MS),
+        }))
+    }
+
+    // size为获取条数，未配置时获取一页
+    fn limit(&self) -> u64 {
+        if self.config.size == 0 { PAGE_SIZE } else { self.config.size }
+    }
+
+    async fn fetch_page(&self, client: &reqwest::Client, query: &str, request: PageRequest) -> Result<Page> {
+        let url = self.config.api_url_or(DEFAULT_API_URL);
+        let per_page = PAGE_SIZE.to_string();
+        let response = send_with_retry("Censys", &self.limiter, self.config.max_retries, || {
+            let mut builder = client.get(url)
+                .basic_auth(&self.api_id, Some(&self.api_secret))
+                .query(&[("q", query), ("per_page", per_page.as_str())]);
+            if !request.cursor.is_empty() {
+                builder = builder.query(&[("cursor", request.cursor.as_str())]);
+            }
+            builder
+        }).await?;
+
+        let status = response.status();
+        let body = response.text()
+            .await
+            .map_err(|e| anyhow::anyhow!("读取Censys API响应失败: {}", e.without_url()))?;
+        if !status.is_success() {
+            return Err(parse_response(&body).err()
+                .unwrap_or_else(|| anyhow::anyhow!("HTTP状态码 {}", status)));
+        }
+
+        let (addresses, next) = parse_response(&body)?;
+        Ok(Page { addresses, size: PAGE_SIZE, total: None, cursor: Some(next) })
+    }
+}
+
+#[async_trait]
+impl ProxySource for CensysSource {
+    fn name(&self) -> &str {
+        self.config.display_name()
+    }
+
+    // Censys每次搜索请求消耗一次查询额度
+    fn estimated_cost(&self) -> Option<u64> {
+        Some(self.limit().div_ceil(PAGE_SIZE))
+    }
+
+    fn quota_budget(&self) -> u64 {
+        self.config.quota_budget
+    }
+
+    async fn remaining_quota(&self) -> Result<Option<u64>> {
+        let url = self.config.api_url_or(DEFAULT_API_URL).replace("/api/v2/hosts/search", "/api/v1/account");
+        let response = send_with_retry("Censys", &self.limiter, self.config.max_retries, || {
+            reqwest::Client::new().get(&url)
+                .basic_auth(&self.api_id, Some(&self.api_secret))
+        }).await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Censys账户信息请求失败: HTTP状态码 {}", response.status()));
+        }
+        let account: CensysAccountResponse = response.json()
+            .await
+            .map_err(|e| anyhow::anyhow!("解析Censys账户信息失败: {}", e.without_url()))?;
+        Ok(Some(account.quota.allowance.saturating_sub(account.quota.used)))
+    }
+
+    async fn fetch(&self) -> Result<Vec<Candidate>> {
+        println!("{}", "从Censys API获取代理列表...".cyan().bold());
+        let query = template::render_query(&self.config)?;
+
+        let client = reqwest::Client::new();
+        let limit = self.limit();
+        let paginator = Paginator { name: "Censys", limit, max_pages: limit.div_ceil(PAGE_SIZE) };
+        let addresses = paginator.fetch(|request| self.fetch_page(&client, &query, request)).await?;
+        Ok(addresses.into_iter().map(|address| Candidate::new(address, self.name(), &query)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler::testutil::MockServer;
+    use base64::{Engine as _, engine::general_purpose};
+
+    const FIXTURE: &str = r#"{
+        "code": 200,
+        "status": "OK",
+        "result": {
+            "total": 3,
+            "hits": [
+                {"ip": "1.2.3.4", "services": [{"port": 22, "service_name": "SSH"}, {"port": 1080, "service_name": "SOCKS"}]},
+                {"ip": "5.6.7.8", "services": [{"port": 80, "service_name": "HTTP"}], "matched_services": [{"port": 8080, "service_name": "HTTP"}]},
+                {"ip": "9.9.9.9", "services": [{"port": 443, "service_name": "HTTP"}]}
+            ],
+            "links": {"prev": "", "next": "cursor-2"}
+        }
+    }"#;
+
+    #[test]
+    fn parses_fixture() {
+        let (proxies, next) = parse_response(FIXTURE).unwrap();
+        assert_eq!(proxies, ["1.2.3.4:1080", "5.6.7.8:8080"]);
+        assert_eq!(next, "cursor-2");
+    }
+
+    #[test]
+    fn parses_empty_and_error_responses() {
+        let (proxies, next) = parse_response(r#"{"code": 200, "status": "OK", "result": {"total": 0, "hits": []}}"#).unwrap();
+        assert!(proxies.is_empty() && next.is_empty());
+
+        let error = parse_response(r#"{"code": 401, "status": "Unauthorized", "error": "You must authenticate"}"#)
+            .unwrap_err().to_string();
+        assert!(error.contains("401") && error.contains("You must authenticate"));
+        assert!(parse_response(r#"{"code": 200, "status": "OK"}"#).is_err());
+    }
+
+    #[test]
+    fn key_requires_id_and_secret() {
+        let config = SourceConfig { kind: "censys".to_string(), key: "only-id".into(), ..SourceConfig::default() };
+        assert!(CensysSource::build(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_basic_auth_and_cursor() {
+        let server = MockServer::start(vec![
+            (200, r#"{"code": 200, "result": {"hits": [{"ip": "1.2.3.4", "matched_services": [{"port": 1080}]}], "links": {"next": "abc"}}}"#),
+            (200, r#"{"code": 200, "result": {"hits": [{"ip": "5.6.7.8", "matched_services": [{"port": 1080}]}], "links": {"next": ""}}}"#),
+        ]).await;
+        let source = CensysSource::build(&SourceConfig {
+            kind: "censys".to_string(),
+            api_url: server.url.clone(),
+            key: "id:secret".into(),
+            query_str: "services.service_name: SOCKS5".to_string(),
+            size: 200,
+            rate_limit_ms: 1,
+            max_retries: 0,
+            ..SourceConfig::default()
+        }).unwrap();
+
+        assert_eq!(source.fetch().await.unwrap().len(), 2);
+        let requests = server.requests();
+        assert_eq!(requests[0].query("cursor"), None);
+        assert_eq!(requests[1].query("cursor").as_deref(), Some("abc"));
+        let expected = format!("Basic {}", general_purpose::STANDARD.encode("id:secret"));
+        assert_eq!(requests[0].headers["authorization"], expected.as_str());
+    }
+}
@@ -0,0 +1,246 @@
+use super::paginate::{Page, PageRequest, Paginator};
+use super::request::{send_with_retry, RateLimiter};
+use super::template;
+use super::{Candidate, ProxySource};
+use crate::config::SourceConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::{Engine as _, engine::general_purpose};
+use colored::*;
+use serde::Deserialize;
+
+pub const DEFAULT_API_URL: &str = "https://fofa.info/api/v1/search/all";
+
+const DEFAULT_PAGE_SIZE: u64 = 1000;
+const DEFAULT_INTERVAL_MS: u64 = 1000;
+// FOFA单页最多返回10000条
+const MAX_PAGE_SIZE: u64 = 10000;
+
+// FOFA API响应结构
+#[derive(Debug, Deserialize)]
+pub struct FofaResponse {
+    pub error: bool,
+    #[serde(default)]
+    pub errmsg: String,
+    #[serde(default)]
+    pub size: u64, // 查询结果总数
+    #[serde(default)]
+    pub results: Vec<Vec<String>>,
+}
+
+// 解析FOFA一页结果，返回代理地址与结果总数
+// 请求字段为ip,port，兼容只返回host一列的旧格式
+pub fn parse_response(body: &str) -> Result<(Vec<String>, u64)> {
+    let fofa_data: FofaResponse = serde_json::from_str(body)
+        .map_err(|e| anyhow::anyhow!("解析FOFA API响应失败: {}", e))?;
+
+    if fofa_data.error {
+        return Err(anyhow::anyhow!("FOFA API返回错误: {}", fofa_data.errmsg));
+    }
+
+    let proxies = fofa_data.results.iter()
+        .filter_map(|row| match row.as_slice() {
+            [ip, port, ..] => Some(format!("{}:{}", ip, port)),
+            [host] => Some(host.clone()),
+            [] => None,
+        })
+        .collect();
+    Ok((proxies, fofa_data.size))
+}
+
+// FOFA账户信息响应结构
+#[derive(Debug, Deserialize)]
+pub struct FofaInfoResponse {
+    #[serde(default)]
+    pub error: bool,
+    #[serde(default)]
+    pub errmsg: String,
+    #[serde(default)]
+    pub remain_api_data: i64, // 剩余可获取的数据条数，负数表示不限
+}
+
+// FOFA按页码翻页，返回从offset开始获取至少wanted条时使用的(页码, 每页条数)
+// 每页条数须整除offset，保证页与已获取的结果首尾衔接，优先取不小于wanted的最小值
+fn page_at(offset: u64, page_size: u64, wanted: u64) -> (u64, u64) {
+    let wanted = wanted.clamp(1, page_size);
+    let size = (wanted..=page_size)
+        .find(|size| offset.is_multiple_of(*size))
+        .or_else(|| (1..wanted).rev().find(|size| offset.is_multiple_of(*size)))
+        .unwrap_or(1);
+    (offset / size + 1, size)
+}
+
+pub struct FofaSource {
+    config: SourceConfig,
+    limiter: RateLimiter,
+}
+
+impl FofaSource {
+    pub fn build(config: &SourceConfig) -> Result<Box<dyn ProxySource>> {
+        Ok(Box::new(FofaSource {
+            config: config.clone(),
+            limiter: RateLimiter::for_source(config, DEFAULT_INTERVAL_MS),
+        }))
+    }
+
+    fn page_size(&self) -> u64 {
+        match self.config.page_size {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        }
+    }
+
+    // size为获取条数，未配置时获取一页
+    fn limit(&self) -> u64 {
+        if self.config.size == 0 { self.page_size() } else { self.config.size }
+    }
+
+    // 只请求还差的条数，避免最后一页多扣F点
+    async fn fetch_page(&self, client: &reqwest::Client, query_base64: &str, request: PageRequest) -> Result<Page> {
+        let (page, size) = page_at(request.offset, self.page_size(), request.wanted);
+        let url = format!(
+            "{}?key={}&qbase64={}&size={}&page={}&fields=ip,port",
+            self.config.api_url_or(DEFAULT_API_URL),
+            self.config.key.expose(),
+            query_base64,
+            size,
+            page
+        );
+        let response = send_with_retry("FOFA", &self.limiter, self.config.max_retries, || {
+            client.get(&url)
+        }).await?;
+
+        let status = response.status();
+        let body = response.text()
+            .await
+            .map_err(|e| anyhow::anyhow!("读取FOFA API响应失败: {}", e.without_url()))?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("FOFA API请求失败: HTTP状态码 {}", status));
+        }
+
+        let (addresses, total) = parse_response(&body)?;
+        Ok(Page { addresses, size, total: Some(total), cursor: None })
+    }
+}
+
+#[async_trait]
+impl ProxySource for FofaSource {
+    fn name(&self) -> &str {
+        self.config.display_name()
+    }
+
+    // FOFA按获取的数据条数计费
+    fn estimated_cost(&self) -> Option<u64> {
+        Some(self.limit())
+    }
+
+    fn quota_budget(&self) -> u64 {
+        self.config.quota_budget
+    }
+
+    async fn remaining_quota(&self) -> Result<Option<u64>> {
+        let url = self.config.api_url_or(DEFAULT_API_URL).replace("/search/all", "/info/my");
+        let response = send_with_retry("FOFA", &self.limiter, self.config.max_retries, || {
+            reqwest::Client::new().get(&url).query(&[("key", self.config.key.expose())])
+        }).await?;
+        let info: FofaInfoResponse = response.json()
+            .await
+            .map_err(|e| anyhow::anyhow!("解析FOFA账户信息失败: {}", e.without_url()))?;
+        if info.error {
+            return Err(anyhow::anyhow!("FOFA API返回错误: {}", info.errmsg));
+        }
+        Ok(u64::try_from(info.remain_api_data).ok())
+    }
+
+    async fn fetch(&self) -> Result<Vec<Candidate>> {
+        println!("{}", "从FOFA API获取代理列表...".cyan().bold());
+        let query = template::render_query(&self.config)?;
+
+        let client = reqwest::Client::new();
+        let query_base64 = general_purpose::STANDARD.encode(&query);
+        let limit = self.limit();
+        let paginator = Paginator { name: "FOFA", limit, max_pages: limit.div_ceil(self.page_size()) };
+        let addresses = paginator.fetch(|request| self.fetch_page(&client, &query_base64, request)).await?;
+        Ok(addresses.into_iter().map(|address| Candidate::new(address, self.name(), &query)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler::testutil::MockServer;
+
+    // 期望的代理地址与结果总数，None表示应返回错误
+    type Expected = Option<(&'static [&'static str], u64)>;
+
+    #[test]
+    fn parses_responses() {
+        let cases: &[(&str, Expected)] = &[
+            (r#"{"error": false, "size": 3, "results": [["1.2.3.4", "1080"], ["5.6.7.8:1081"], []]}"#,
+                Some((&["1.2.3.4:1080", "5.6.7.8:1081"], 3))),
+            (r#"{"error": false, "size": 0, "results": []}"#, Some((&[], 0))),
+            (r#"{"error": true, "errmsg": "[820031] F点余额不足"}"#, None),
+            (r#"{"results": []}"#, None),
+            ("not json", None),
+        ];
+        for (body, expected) in cases {
+            match (parse_response(body), expected) {
+                (Ok((proxies, total)), Some((expected, expected_total))) => {
+                    assert_eq!(proxies, *expected, "{}", body);
+                    assert_eq!(total, *expected_total, "{}", body);
+                }
+                (Err(_), None) => {}
+                (result, _) => panic!("{} -> {:?}", body, result),
+            }
+        }
+    }
+
+    #[test]
+    fn page_size_is_capped() {
+        let config = |page_size| SourceConfig { page_size, ..SourceConfig::default() };
+        let size = |page_size| FofaSource { limiter: RateLimiter::new(0), config: config(page_size) }.page_size();
+        assert_eq!(size(0), DEFAULT_PAGE_SIZE);
+        assert_eq!(size(50), 50);
+        assert_eq!(size(20000), MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn page_at_aligns_smaller_pages_to_offset() {
+        let cases: &[(u64, u64, u64, (u64, u64))] = &[
+            (0, 1000, 100, (1, 100)),
+            (0, 1000, 5000, (1, 1000)),
+            (100, 100, 50, (3, 50)),
+            (100, 100, 30, (3, 50)),
+            (100, 100, 100, (2, 100)),
+            (150, 100, 100, (3, 75)),
+        ];
+        for &(offset, page_size, wanted, expected) in cases {
+            assert_eq!(page_at(offset, page_size, wanted), expected, "{} {} {}", offset, page_size, wanted);
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_only_the_remaining_rows() {
+        let server = MockServer::start(vec![
+            (200, r#"{"error": false, "size": 1000, "results": [["1.2.3.4", "1080"], ["5.6.7.8", "1080"]]}"#),
+            (200, r#"{"error": false, "size": 1000, "results": [["9.9.9.9", "1080"]]}"#),
+        ]).await;
+        let source = FofaSource::build(&SourceConfig {
+            kind: "fofa".to_string(),
+            api_url: server.url.clone(),
+            key: "test-key".into(),
+            query_str: "protocol==\"socks5\"".to_string(),
+            size: 3,
+            page_size: 2,
+            rate_limit_ms: 1,
+            max_retries: 0,
+            ..SourceConfig::default()
+        }).unwrap();
+
+        assert_eq!(source.fetch().await.unwrap().len(), 3);
+        let requests = server.requests();
+        assert_eq!(requests[0].query("key").as_deref(), Some("test-key"));
+        // 第二页只差1条，按每页1条请求第3页，与第一页的2条首尾衔接
+        assert_eq!((requests[1].query("page").as_deref(), requests[1].query("size").as_deref()), (Some("3"), Some("1")));
+    }
+}
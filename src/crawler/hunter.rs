@@ -0,0 +1,161 @@
+use super::request::{send_with_retry, RateLimiter};
+use super::template;
+use super::{is_quota_error, Candidate, ProxySource};
+use crate::config::SourceConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::{Engine as _, engine::general_purpose};
+use colored::*;
+use serde::Deserialize;
+use std::sync::Mutex;
+
+pub const DEFAULT_API_URL: &str = "https://hunter.qianxin.com/openApi/search";
+
+// Hunter每页固定请求100条
+const PAGE_SIZE: u64 = 100;
+const DEFAULT_INTERVAL_MS: u64 = 200;
+
+// Hunter API响应结构
+#[derive(Debug, Deserialize)]
+pub struct HunterResponse {
+    pub code: u32,
+    pub message: String,
+    #[serde(default)]
+    pub data: Option<HunterData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HunterData {
+    pub total: u64,
+    #[serde(default)]
+    pub arr: Option<Vec<HunterItem>>, // 无结果时为null
+    #[serde(default)]
+    pub rest_quota: String, // 例如 "剩余积分：9868"
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HunterItem {
+    pub ip: String,
+    pub port: u32,
+}
+
+// 从rest_quota文本中提取剩余积分
+fn parse_rest_quota(text: &str) -> Option<u64> {
+    let digits: String = text.chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+pub struct HunterSource {
+    config: SourceConfig,
+    limiter: RateLimiter,
+    rest_quota: Mutex<Option<u64>>, // 最近一次响应中报告的剩余积分
+}
+
+impl HunterSource {
+    pub fn build(config: &SourceConfig) -> Result<Box<dyn ProxySource>> {
+        Ok(Box::new(HunterSource {
+            config: config.clone(),
+            limiter: RateLimiter::for_source(config, DEFAULT_INTERVAL_MS),
+            rest_quota: Mutex::new(None),
+        }))
+    }
+}
+
+#[async_trait]
+impl ProxySource for HunterSource {
+    fn name(&self) -> &str {
+        self.config.display_name()
+    }
+
+    // Hunter按返回的数据条数扣除积分，size为页数
+    fn estimated_cost(&self) -> Option<u64> {
+        Some(self.config.size * PAGE_SIZE)
+    }
+
+    fn quota_budget(&self) -> u64 {
+        self.config.quota_budget
+    }
+
+    // Hunter没有单独的积分查询接口，剩余积分随搜索结果返回
+    fn reported_quota(&self) -> Option<u64> {
+        *self.rest_quota.lock().unwrap()
+    }
+
+    async fn fetch(&self) -> Result<Vec<Candidate>> {
+        println!("{}", "从Hunter API获取代理列表...".cyan().bold());
+        let query = template::render_query(&self.config)?;
+
+        let query_base64 = general_purpose::STANDARD.encode(&query);
+        let mut all_proxies = Vec::new();
+        let client = reqwest::Client::new();
+
+        // 遍历所有页，Hunter的size表示页数
+        for page in 1..=self.config.size {
+            let url = format!(
+                "{}?api-key={}&search={}&page={}&page_size={}",
+                self.config.api_url_or(DEFAULT_API_URL),
+                self.config.key.expose(),
+                query_base64,
+                page,
+                PAGE_SIZE
+            );
+
+            let response = match send_with_retry("Hunter", &self.limiter, self.config.max_retries, || {
+                client.get(&url)
+            }).await {
+                Ok(response) => response,
+                Err(e) => {
+                    eprintln!("{} {}", format!("Hunter API请求第{}页失败:", page).red().bold(), e);
+                    continue; // 继续下一页而不是完全中止
+                }
+            };
+
+            if !response.status().is_success() {
+                eprintln!("{} {}", format!("Hunter API请求第{}页失败: HTTP状态码", page).red().bold(), response.status());
+                continue; // 继续下一页而不是完全中止
+            }
+
+            let hunter_data: HunterResponse = match response.json().await {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("{} {}", format!("解析Hunter API响应失败 (第{}页):", page).red().bold(), e.without_url());
+                    continue; // 继续下一页
+                }
+            };
+
+            if hunter_data.code != 200 {
+                // 积分耗尽时后续页也不会成功，直接停止翻页
+                if is_quota_error(&hunter_data.message) {
+                    eprintln!("{} {}", "Hunter积分已耗尽，停止翻页:".yellow().bold(), hunter_data.message);
+                    break;
+                }
+                eprintln!("{} {}", format!("Hunter API返回错误 (第{}页):", page).red().bold(), hunter_data.message);
+                continue; // 继续下一页
+            }
+
+            let Some(data) = hunter_data.data else { break };
+            if let Some(quota) = parse_rest_quota(&data.rest_quota) {
+                *self.rest_quota.lock().unwrap() = Some(quota);
+            }
+
+            // 提取代理
+            let items = data.arr.unwrap_or_default();
+            if items.is_empty() {
+                break;
+            }
+            for item in items {
+                let proxy = format!("{}:{}", item.ip, item.port);
+                all_proxies.push(Candidate::new(proxy, self.name(), &query));
+            }
+
+            if page * PAGE_SIZE >= data.total {
+                break;
+            }
+        }
+
+        Ok(all_proxies)
+    }
+}
@@ -0,0 +1,187 @@
+use super::is_quota_error;
+use anyhow::Result;
+use colored::*;
+use std::collections::HashSet;
+use std::future::Future;
+
+// 请求一页时的翻页状态
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageRequest {
+    pub number: u64,    // 页码，从1开始
+    pub offset: u64,    // 之前各页请求的条数之和
+    pub wanted: u64,    // 距离获取条数还差的条数
+    pub cursor: String, // 上一页返回的游标，第一页为空
+}
+
+// 一页的结果
+#[derive(Debug, Default)]
+pub struct Page {
+    pub addresses: Vec<String>,
+    pub size: u64,              // 本页请求的条数
+    pub total: Option<u64>,     // 结果总数，None表示API不返回总数
+    pub cursor: Option<String>, // 按游标翻页的API返回的下一页游标，为空表示没有下一页
+}
+
+// 通用的翻页流程：第一页失败直接报错，后续页失败(包括配额耗尽)时保留已获取的结果
+// 遇到空页、达到获取条数或结果总数、游标为空或重复、达到最大页数时停止翻页
+pub struct Paginator<'a> {
+    pub name: &'a str,  // 输出中显示的API名称
+    pub limit: u64,     // 获取条数
+    pub max_pages: u64, // 最多请求的页数
+}
+
+impl Paginator<'_> {
+    // 逐页调用fetch_page，返回去重后的代理地址
+    pub async fn fetch<F, Fut>(&self, mut fetch_page: F) -> Result<Vec<String>>
+    where
+        F: FnMut(PageRequest) -> Fut,
+        Fut: Future<Output = Result<Page>>,
+    {
+        let mut seen = HashSet::new();
+        let mut cursors = HashSet::new();
+        let mut proxies = Vec::new();
+        let mut request = PageRequest { number: 1, offset: 0, wanted: self.limit, cursor: String::new() };
+
+        while request.number <= self.max_pages {
+            let page = match fetch_page(request.clone()).await {
+                Ok(page) => page,
+                Err(e) if request.number == 1 => return Err(e),
+                Err(e) => {
+                    if is_quota_error(&e.to_string()) {
+                        eprintln!("{} {}", format!("{}配额已耗尽，停止翻页:", self.name).yellow().bold(), e);
+                    } else {
+                        eprintln!("{} {}", format!("{} API请求第{}页失败:", self.name, request.number).red().bold(), e);
+                    }
+                    break;
+                }
+            };
+
+            if page.addresses.is_empty() {
+                break;
+            }
+            let count = page.addresses.len();
+            for address in page.addresses {
+                if seen.insert(address.clone()) {
+                    proxies.push(address);
+                }
+            }
+            let total = page.total.map_or(self.limit, |total| self.limit.min(total));
+            print_page_progress(self.name, request.number, count, proxies.len(), total);
+
+            request.offset += page.size;
+            if proxies.len() as u64 >= self.limit || page.total.is_some_and(|total| request.offset >= total) {
+                break;
+            }
+            // 游标重复说明API在原地翻页，继续请求只会浪费配额
+            if let Some(cursor) = page.cursor {
+                if cursor.is_empty() || !cursors.insert(cursor.clone()) {
+                    break;
+                }
+                request.cursor = cursor;
+            }
+            request.number += 1;
+            request.wanted = self.limit - proxies.len() as u64;
+        }
+
+        proxies.truncate(self.limit as usize);
+        Ok(proxies)
+    }
+}
+
+// 输出翻页进度
+fn print_page_progress(name: &str, page: u64, count: usize, collected: usize, limit: u64) {
+    println!("  {} {} {} {}",
+        format!("{} 第{}页:", name, page).cyan(),
+        format!("获取{}条", count).green(),
+        "累计".green(),
+        format!("{}/{}", collected, limit).yellow()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // 模拟的一页结果：返回条数、结果总数、游标，None表示请求失败
+    type MockPage = Option<(u64, Option<u64>, Option<&'static str>)>;
+    // 获取条数、最大页数、各页结果、期望的代理数与请求页数，None表示应返回错误
+    type Case = (u64, u64, &'static [MockPage], Option<usize>, usize);
+
+    const fn ok(count: u64, total: u64) -> MockPage {
+        Some((count, Some(total), None))
+    }
+
+    const CASES: &[Case] = &[
+        // 达到结果总数
+        (1000, 10, &[ok(100, 150), ok(50, 150), ok(100, 150)], Some(150), 2),
+        // 达到获取条数，多余的结果被截断
+        (120, 10, &[ok(100, 1000), ok(100, 1000), ok(100, 1000)], Some(120), 2),
+        // 空页
+        (500, 10, &[ok(100, 1000), ok(0, 1000), ok(100, 1000)], Some(100), 2),
+        // 最大页数
+        (1000, 2, &[ok(100, 5000), ok(100, 5000), ok(100, 5000)], Some(200), 2),
+        // 第一页失败报错，后续页失败保留结果
+        (100, 10, &[None], None, 1),
+        (300, 10, &[ok(100, 300), None, ok(100, 300)], Some(100), 2),
+        // 游标为空或重复
+        (1000, 10, &[Some((100, None, Some("a"))), Some((30, None, Some("")))], Some(130), 2),
+        (1000, 10, &[Some((10, None, Some("a"))), Some((10, None, Some("a"))), Some((10, None, Some("b")))], Some(20), 2),
+        // 重复地址只计一次，按获取条数继续翻页
+        (150, 10, &[ok(100, 1000), Some((100, Some(1000), Some("dup"))), ok(100, 1000)], Some(150), 3),
+    ];
+
+    #[tokio::test]
+    async fn stops_at_the_right_page() {
+        for (i, &(limit, max_pages, pages, expected, expected_requests)) in CASES.iter().enumerate() {
+            let requests = Mutex::new(Vec::new());
+            let paginator = Paginator { name: "Test", limit, max_pages };
+            let result = paginator.fetch(|request: PageRequest| {
+                requests.lock().unwrap().push(request.clone());
+                let page = pages.get(request.number as usize - 1).copied().flatten();
+                async move {
+                    let (count, total, cursor) = page.ok_or_else(|| anyhow::anyhow!("请求失败"))?;
+                    // 游标为dup的页与第一页内容相同
+                    let number = if cursor == Some("dup") { 1 } else { request.number };
+                    Ok(Page {
+                        addresses: (0..count).map(|j| format!("10.{}.{}.{}:1080", number, j / 256, j % 256)).collect(),
+                        size: count,
+                        total,
+                        cursor: cursor.map(str::to_string),
+                    })
+                }
+            }).await;
+
+            assert_eq!(result.as_ref().ok().map(Vec::len), expected, "case {}", i);
+            assert_eq!(requests.lock().unwrap().len(), expected_requests, "case {}", i);
+        }
+    }
+
+    #[tokio::test]
+    async fn passes_offset_wanted_and_cursor() {
+        let requests = Mutex::new(Vec::new());
+        let paginator = Paginator { name: "Test", limit: 250, max_pages: 3 };
+        let proxies = paginator.fetch(|request: PageRequest| {
+            requests.lock().unwrap().push(request.clone());
+            async move {
+                let size = request.wanted.min(100);
+                Ok(Page {
+                    addresses: (0..size).map(|j| format!("10.0.{}.{}:1080", request.number, j)).collect(),
+                    size,
+                    total: Some(1000),
+                    cursor: Some(format!("c{}", request.number)),
+                })
+            }
+        }).await.unwrap();
+
+        assert_eq!(proxies.len(), 250);
+        let requests: Vec<(u64, u64, u64, String)> = requests.into_inner().unwrap().into_iter()
+            .map(|r| (r.number, r.offset, r.wanted, r.cursor))
+            .collect();
+        assert_eq!(requests, [
+            (1, 0, 250, String::new()),
+            (2, 100, 150, "c1".to_string()),
+            (3, 200, 50, "c2".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,238 @@
+use super::paginate::{Page, PageRequest, Paginator};
+use super::request::{send_with_retry, RateLimiter};
+use super::template;
+use super::{Candidate, ProxySource};
+use crate::config::SourceConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+use colored::*;
+use serde::Deserialize;
+use serde_json::Value;
+
+pub const DEFAULT_API_URL: &str = "https://quake.360.net/api/v3/search/quake_service";
+
+const DEFAULT_PAGE_SIZE: u64 = 100;
+const DEFAULT_INTERVAL_MS: u64 = 1000;
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/83.0.4103.116 Safari/537.36";
+// Quake单页最多返回500条
+const MAX_PAGE_SIZE: u64 = 500;
+
+// Quake API响应结构
+#[derive(Debug, Deserialize)]
+pub struct QuakeResponse {
+    pub code: Value, // 成功时为0，错误时可能为字符串错误码，例如 q3005
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub data: Vec<QuakeItem>,
+    #[serde(default)]
+    pub meta: QuakeMeta,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuakeItem {
+    pub ip: String,
+    pub port: u32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct QuakeMeta {
+    #[serde(default)]
+    pub pagination: QuakePagination,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct QuakePagination {
+    #[serde(default)]
+    pub total: u64,
+}
+
+// 解析Quake一页结果，返回代理地址与结果总数
+pub fn parse_response(body: &str) -> Result<(Vec<String>, u64)> {
+    let quake_data: QuakeResponse = serde_json::from_str(body)
+        .map_err(|e| anyhow::anyhow!("解析Quake API响应失败: {}", e))?;
+
+    if quake_data.code != 0 {
+        return Err(anyhow::anyhow!("Quake API返回错误({}): {}", quake_data.code, quake_data.message));
+    }
+
+    let proxies = quake_data.data.iter()
+        .map(|item| format!("{}:{}", item.ip, item.port))
+        .collect();
+    Ok((proxies, quake_data.meta.pagination.total))
+}
+
+// Quake用户信息响应结构
+#[derive(Debug, Deserialize)]
+pub struct QuakeUserResponse {
+    pub code: Value,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub data: QuakeUserData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct QuakeUserData {
+    #[serde(default)]
+    pub month_remaining_credit: u64, // 当月剩余积分
+    #[serde(default)]
+    pub persistent_credit: u64,      // 长效积分
+}
+
+pub struct QuakeSource {
+    config: SourceConfig,
+    limiter: RateLimiter,
+}
+
+impl QuakeSource {
+    pub fn build(config: &SourceConfig) -> Result<Box<dyn ProxySource>> {
+        Ok(Box::new(QuakeSource {
+            config: config.clone(),
+            limiter: RateLimiter::for_source(config, DEFAULT_INTERVAL_MS),
+        }))
+    }
+
+    fn page_size(&self) -> u64 {
+        match self.config.page_size {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        }
+    }
+
+    // size为获取条数，未配置时获取一页
+    fn limit(&self) -> u64 {
+        if self.config.size == 0 { self.page_size() } else { self.config.size }
+    }
+
+    // 只请求还差的条数，避免最后一页多扣积分
+    async fn fetch_page(&self, client: &reqwest::Client, query: &str, request: PageRequest) -> Result<Page> {
+        let url = self.config.api_url_or(DEFAULT_API_URL);
+        let size = self.page_size().min(request.wanted);
+        let request_body = serde_json::json!({
+            "query": query,
+            "latest": "True",
+            "start": request.offset,
+            "size": size,
+            "include": ["ip", "port"]
+        });
+        let response = send_with_retry("Quake", &self.limiter, self.config.max_retries, || {
+            client.post(url)
+                .header("Content-Type", "application/json")
+                .header("User-Agent", USER_AGENT)
+                .header("X-QuakeToken", self.config.key.expose())
+                .json(&request_body)
+        }).await?;
+
+        let status = response.status();
+        let body = response.text()
+            .await
+            .map_err(|e| anyhow::anyhow!("读取Quake API响应失败: {}", e.without_url()))?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("Quake API请求失败: HTTP状态码 {}", status));
+        }
+
+        let (addresses, total) = parse_response(&body)?;
+        Ok(Page { addresses, size, total: Some(total), cursor: None })
+    }
+}
+
+#[async_trait]
+impl ProxySource for QuakeSource {
+    fn name(&self) -> &str {
+        self.config.display_name()
+    }
+
+    // Quake按返回的数据条数扣除积分
+    fn estimated_cost(&self) -> Option<u64> {
+        Some(self.limit())
+    }
+
+    fn quota_budget(&self) -> u64 {
+        self.config.quota_budget
+    }
+
+    async fn remaining_quota(&self) -> Result<Option<u64>> {
+        let url = self.config.api_url_or(DEFAULT_API_URL).replace("/search/quake_service", "/user/info");
+        let response = send_with_retry("Quake", &self.limiter, self.config.max_retries, || {
+            reqwest::Client::new().get(&url)
+                .header("User-Agent", USER_AGENT)
+                .header("X-QuakeToken", self.config.key.expose())
+        }).await?;
+        let info: QuakeUserResponse = response.json()
+            .await
+            .map_err(|e| anyhow::anyhow!("解析Quake用户信息失败: {}", e.without_url()))?;
+        if info.code != 0 {
+            return Err(anyhow::anyhow!("Quake API返回错误({}): {}", info.code, info.message));
+        }
+        Ok(Some(info.data.month_remaining_credit + info.data.persistent_credit))
+    }
+
+    async fn fetch(&self) -> Result<Vec<Candidate>> {
+        println!("{}", "从Quake API获取代理列表...".cyan().bold());
+        let query = template::render_query(&self.config)?;
+
+        let client = reqwest::Client::new();
+        let limit = self.limit();
+        let paginator = Paginator { name: "Quake", limit, max_pages: limit.div_ceil(self.page_size()) };
+        let addresses = paginator.fetch(|request| self.fetch_page(&client, &query, request)).await?;
+        Ok(addresses.into_iter().map(|address| Candidate::new(address, self.name(), &query)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler::testutil::MockServer;
+
+    // 期望的代理地址与结果总数，None表示应返回错误
+    type Expected = Option<(&'static [&'static str], u64)>;
+
+    #[test]
+    fn parses_responses() {
+        let cases: &[(&str, Expected)] = &[
+            (r#"{"code": 0, "data": [{"ip": "1.2.3.4", "port": 1080}], "meta": {"pagination": {"total": 7}}}"#,
+                Some((&["1.2.3.4:1080"], 7))),
+            (r#"{"code": 0, "data": []}"#, Some((&[], 0))),
+            (r#"{"code": "q3005", "message": "积分不足"}"#, None),
+            (r#"{"code": 1, "message": "error"}"#, None),
+            (r#"{"data": []}"#, None),
+        ];
+        for (body, expected) in cases {
+            match (parse_response(body), expected) {
+                (Ok((proxies, total)), Some((expected, expected_total))) => {
+                    assert_eq!(proxies, *expected, "{}", body);
+                    assert_eq!(total, *expected_total, "{}", body);
+                }
+                (Err(_), None) => {}
+                (result, _) => panic!("{} -> {:?}", body, result),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_token_start_and_remaining_size() {
+        let page = |ip| format!(r#"{{"code": 0, "data": [{{"ip": "{}", "port": 1080}}], "meta": {{"pagination": {{"total": 1000}}}}}}"#, ip);
+        let server = MockServer::start(vec![(200, page("1.2.3.4")), (200, page("5.6.7.8"))]).await;
+        let source = QuakeSource::build(&SourceConfig {
+            kind: "quake".to_string(),
+            api_url: server.url.clone(),
+            key: "test-key".into(),
+            query_str: "service:socks5".to_string(),
+            size: 150,
+            page_size: 100,
+            rate_limit_ms: 1,
+            max_retries: 0,
+            ..SourceConfig::default()
+        }).unwrap();
+
+        assert_eq!(source.fetch().await.unwrap().len(), 2);
+        let requests = server.requests();
+        assert_eq!(requests[0].headers["X-QuakeToken"], "test-key");
+        let pages: Vec<(u64, u64)> = requests.iter()
+            .map(|request| serde_json::from_str::<Value>(&request.body).unwrap())
+            .map(|body| (body["start"].as_u64().unwrap(), body["size"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(pages, [(0, 100), (100, 100)]);
+    }
+}
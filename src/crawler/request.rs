@@ -0,0 +1,94 @@
+use crate::config::SourceConfig;
+use anyhow::Result;
+use colored::*;
+use reqwest::StatusCode;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+// 退避等待的上限
+const MAX_BACKOFF_SECS: u64 = 60;
+
+// 简单的请求速率限制器，保证相邻两次请求间隔不小于interval
+pub struct RateLimiter {
+    interval: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(interval_ms: u64) -> Self {
+        RateLimiter {
+            interval: Duration::from_millis(interval_ms),
+            last: Mutex::new(None),
+        }
+    }
+
+    // 按代理源配置的rate_limit_ms构造，未配置时使用该源的默认间隔
+    pub fn for_source(config: &SourceConfig, default_interval_ms: u64) -> Self {
+        match config.rate_limit_ms {
+            0 => RateLimiter::new(default_interval_ms),
+            interval => RateLimiter::new(interval),
+        }
+    }
+
+    pub async fn wait(&self) {
+        let mut last = self.last.lock().await;
+        if let Some(last) = *last {
+            tokio::time::sleep_until(last + self.interval).await;
+        }
+        *last = Some(Instant::now());
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs((1u64 << attempt.min(6)).min(MAX_BACKOFF_SECS))
+}
+
+// 按速率限制发送API请求，遇到网络错误、429或5xx时指数退避重试
+// 429响应带有Retry-After时按其等待
+pub async fn send_with_retry<F>(name: &str, limiter: &RateLimiter, max_retries: u32, build: F) -> Result<reqwest::Response>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let mut attempt = 0;
+    loop {
+        limiter.wait().await;
+
+        let wait = match build().send().await {
+            Ok(response) if !is_retryable(response.status()) || attempt >= max_retries => {
+                return Ok(response);
+            }
+            Ok(response) => {
+                let retry_after = response.headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .map(|secs| Duration::from_secs(secs.min(MAX_BACKOFF_SECS)));
+                println!("{} {}",
+                    format!("{} API返回{}，等待后重试:", name, response.status()).yellow().bold(),
+                    format!("{}/{}", attempt + 1, max_retries).yellow()
+                );
+                retry_after.unwrap_or_else(|| backoff(attempt))
+            }
+            // 请求地址中可能带有API key，错误信息中不包含地址
+            Err(e) if attempt >= max_retries => {
+                return Err(anyhow::anyhow!("发送{} API请求失败: {}", name, e.without_url()));
+            }
+            Err(e) => {
+                println!("{} {} ({})",
+                    format!("{} API请求出错，等待后重试:", name).yellow().bold(),
+                    format!("{}/{}", attempt + 1, max_retries).yellow(),
+                    e.without_url()
+                );
+                backoff(attempt)
+            }
+        };
+
+        tokio::time::sleep(wait).await;
+        attempt += 1;
+    }
+}
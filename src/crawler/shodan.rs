@@ -0,0 +1,196 @@
+use super::paginate::{Page, PageRequest, Paginator};
+use super::request::{send_with_retry, RateLimiter};
+use super::template;
+use super::{Candidate, ProxySource};
+use crate::config::SourceConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+use colored::*;
+use serde::Deserialize;
+
+pub const DEFAULT_API_URL: &str = "https://api.shodan.io/shodan/host/search";
+
+// Shodan每页固定返回100条
+const PAGE_SIZE: u64 = 100;
+// Shodan要求搜索请求间隔至少1秒
+const DEFAULT_INTERVAL_MS: u64 = 1000;
+
+// Shodan API响应结构
+#[derive(Debug, Deserialize)]
+pub struct ShodanResponse {
+    #[serde(default)]
+    pub matches: Vec<ShodanMatch>,
+    #[serde(default)]
+    pub total: u64,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShodanMatch {
+    pub ip_str: String,
+    pub port: u16,
+}
+
+// 解析Shodan一页结果，返回代理地址与结果总数
+pub fn parse_response(body: &str) -> Result<(Vec<String>, u64)> {
+    let data: ShodanResponse = serde_json::from_str(body)
+        .map_err(|e| anyhow::anyhow!("解析Shodan API响应失败: {}", e))?;
+
+    if let Some(error) = data.error {
+        return Err(anyhow::anyhow!("Shodan API返回错误: {}", error));
+    }
+
+    let proxies = data.matches.iter()
+        .map(|m| format!("{}:{}", m.ip_str, m.port))
+        .collect();
+    Ok((proxies, data.total))
+}
+
+// Shodan账户信息响应结构
+#[derive(Debug, Deserialize)]
+pub struct ShodanInfoResponse {
+    #[serde(default)]
+    pub query_credits: u64,
+}
+
+pub struct ShodanSource {
+    config: SourceConfig,
+    limiter: RateLimiter,
+}
+
+impl ShodanSource {
+    pub fn build(config: &SourceConfig) -> Result<Box<dyn ProxySource>> {
+        Ok(Box::new(ShodanSource {
+            config: config.clone(),
+            limiter: RateLimiter::for_source(config, DEFAULT_INTERVAL_MS),
+        }))
+    }
+
+    // size为获取条数，未配置时获取一页
+    fn limit(&self) -> u64 {
+        if self.config.size == 0 { PAGE_SIZE } else { self.config.size }
+    }
+
+    async fn fetch_page(&self, client: &reqwest::Client, query: &str, request: PageRequest) -> Result<Page> {
+        let url = self.config.api_url_or(DEFAULT_API_URL);
+        let response = send_with_retry("Shodan", &self.limiter, self.config.max_retries, || {
+            client.get(url).query(&[
+                ("key", self.config.key.expose()),
+                ("query", query),
+                ("page", &request.number.to_string()),
+                ("minify", "true"),
+            ])
+        }).await?;
+
+        let status = response.status();
+        let body = response.text()
+            .await
+            .map_err(|e| anyhow::anyhow!("读取Shodan API响应失败: {}", e.without_url()))?;
+        if !status.is_success() {
+            return Err(parse_response(&body).err()
+                .unwrap_or_else(|| anyhow::anyhow!("HTTP状态码 {}", status)));
+        }
+
+        let (addresses, total) = parse_response(&body)?;
+        Ok(Page { addresses, size: PAGE_SIZE, total: Some(total), cursor: None })
+    }
+}
+
+#[async_trait]
+impl ProxySource for ShodanSource {
+    fn name(&self) -> &str {
+        self.config.display_name()
+    }
+
+    // 带过滤条件或翻页的搜索每页消耗一个query credit
+    fn estimated_cost(&self) -> Option<u64> {
+        Some(self.limit().div_ceil(PAGE_SIZE))
+    }
+
+    fn quota_budget(&self) -> u64 {
+        self.config.quota_budget
+    }
+
+    async fn remaining_quota(&self) -> Result<Option<u64>> {
+        let url = self.config.api_url_or(DEFAULT_API_URL).replace("/shodan/host/search", "/api-info");
+        let response = send_with_retry("Shodan", &self.limiter, self.config.max_retries, || {
+            reqwest::Client::new().get(&url).query(&[("key", self.config.key.expose())])
+        }).await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Shodan账户信息请求失败: HTTP状态码 {}", response.status()));
+        }
+        let info: ShodanInfoResponse = response.json()
+            .await
+            .map_err(|e| anyhow::anyhow!("解析Shodan账户信息失败: {}", e.without_url()))?;
+        Ok(Some(info.query_credits))
+    }
+
+    async fn fetch(&self) -> Result<Vec<Candidate>> {
+        println!("{}", "从Shodan API获取代理列表...".cyan().bold());
+        let query = template::render_query(&self.config)?;
+
+        let client = reqwest::Client::new();
+        let limit = self.limit();
+        let paginator = Paginator { name: "Shodan", limit, max_pages: limit.div_ceil(PAGE_SIZE) };
+        let addresses = paginator.fetch(|request| self.fetch_page(&client, &query, request)).await?;
+        Ok(addresses.into_iter().map(|address| Candidate::new(address, self.name(), &query)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler::testutil::MockServer;
+
+    const FIXTURE: &str = r#"{
+        "matches": [
+            {"ip_str": "1.2.3.4", "port": 1080, "org": "Example"},
+            {"ip_str": "5.6.7.8", "port": 10808}
+        ],
+        "total": 2
+    }"#;
+
+    #[test]
+    fn parses_fixture() {
+        let (proxies, total) = parse_response(FIXTURE).unwrap();
+        assert_eq!(proxies, ["1.2.3.4:1080", "5.6.7.8:10808"]);
+        assert_eq!(total, 2);
+    }
+
+    #[test]
+    fn parses_empty_and_error_responses() {
+        assert_eq!(parse_response(r#"{"matches": [], "total": 0}"#).unwrap(), (vec![], 0));
+        assert_eq!(parse_response("{}").unwrap(), (vec![], 0));
+
+        let error = parse_response(r#"{"error": "Invalid API key"}"#).unwrap_err().to_string();
+        assert!(error.contains("Invalid API key"));
+        assert!(parse_response("<html>502</html>").is_err());
+        assert!(parse_response(r#"{"matches": [{"ip_str": "1.2.3.4"}]}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_key_query_and_page() {
+        let server = MockServer::start(vec![
+            (200, r#"{"matches": [{"ip_str": "1.2.3.4", "port": 1080}], "total": 1000}"#),
+            (200, r#"{"matches": [{"ip_str": "5.6.7.8", "port": 1080}], "total": 1000}"#),
+        ]).await;
+        let source = ShodanSource::build(&SourceConfig {
+            kind: "shodan".to_string(),
+            api_url: server.url.clone(),
+            key: "test-key".into(),
+            query_str: "product:socks5".to_string(),
+            size: 200,
+            rate_limit_ms: 1,
+            max_retries: 0,
+            ..SourceConfig::default()
+        }).unwrap();
+
+        let candidates = source.fetch().await.unwrap();
+        assert_eq!(candidates.iter().map(|c| c.address.as_str()).collect::<Vec<_>>(), ["1.2.3.4:1080", "5.6.7.8:1080"]);
+        assert_eq!(candidates[0].query, "product:socks5");
+        let requests = server.requests();
+        assert_eq!(requests[1].query("page").as_deref(), Some("2"));
+        assert_eq!(requests[1].query("key").as_deref(), Some("test-key"));
+    }
+}
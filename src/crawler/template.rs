@@ -0,0 +1,138 @@
+use crate::config::SourceConfig;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local};
+use std::collections::BTreeMap;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// 在爬取时渲染代理源的查询语句
+pub fn render_query(config: &SourceConfig) -> Result<String> {
+    render(&config.query_str, &config.vars, Local::now())
+        .map_err(|e| anyhow::anyhow!("渲染{}的查询语句失败: {}", config.display_name(), e))
+}
+
+// 替换模板中的 {{变量}} 占位符
+// 内置变量:
+//   today        当天日期，默认格式 2025-02-25
+//   now          当前时间，默认格式 2025-02-25 08:00:00
+// 内置变量可以带偏移量与格式，例如 {{today-7d}}、{{now-12h}}、{{today|%Y%m%d}}，偏移单位: h / d / w
+// 其余变量从代理源的vars中查找，未定义的变量视为错误
+pub fn render(template: &str, vars: &BTreeMap<String, String>, now: DateTime<Local>) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find("}}")
+            .ok_or_else(|| anyhow::anyhow!("占位符缺少结束的}}}}: {}", &rest[start..]))?;
+        output.push_str(&expand(after[..end].trim(), vars, now)?);
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn expand(expr: &str, vars: &BTreeMap<String, String>, now: DateTime<Local>) -> Result<String> {
+    if let Some(value) = vars.get(expr) {
+        return Ok(value.clone());
+    }
+
+    let (expr, format) = match expr.split_once('|') {
+        Some((expr, format)) => (expr.trim(), Some(format.trim())),
+        None => (expr, None),
+    };
+    let name_end = expr.find(['+', '-']).unwrap_or(expr.len());
+    let (name, offset) = expr.split_at(name_end);
+    let default_format = match name.trim() {
+        "today" => DATE_FORMAT,
+        "now" => DATETIME_FORMAT,
+        other => return Err(anyhow::anyhow!("未定义的变量: {}", other)),
+    };
+
+    let time = now.checked_add_signed(parse_offset(offset.trim())?)
+        .ok_or_else(|| anyhow::anyhow!("时间偏移超出范围: {}", offset.trim()))?;
+    let format = format.unwrap_or(default_format);
+    if chrono::format::StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error) {
+        return Err(anyhow::anyhow!("无效的时间格式: {}", format));
+    }
+    Ok(time.format(format).to_string())
+}
+
+// 解析偏移量，例如 -7d、+12h、-2w
+fn parse_offset(offset: &str) -> Result<Duration> {
+    if offset.is_empty() {
+        return Ok(Duration::zero());
+    }
+
+    let invalid = || anyhow::anyhow!("无效的时间偏移: {}", offset);
+    let (sign, body) = if let Some(body) = offset.strip_prefix('+') {
+        (1, body.trim())
+    } else if let Some(body) = offset.strip_prefix('-') {
+        (-1, body.trim())
+    } else {
+        return Err(invalid());
+    };
+    // 单位可能是多字节字符(例如误写成 7天)，按字符而不是字节拆分
+    let (unit_start, unit) = body.char_indices().last().ok_or_else(invalid)?;
+    let amount: i64 = body[..unit_start].trim().parse().map_err(|_| invalid())?;
+    let amount = amount.checked_mul(sign).ok_or_else(invalid)?;
+    let duration = match unit {
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => return Err(invalid()),
+    };
+    duration.ok_or_else(|| anyhow::anyhow!("时间偏移超出范围: {}", offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // 模板、变量与期望的渲染结果，None表示应返回错误
+    type Case<'a> = (&'a str, &'a BTreeMap<String, String>, Option<&'a str>);
+
+    #[test]
+    fn renders_placeholders() {
+        let now = Local.with_ymd_and_hms(2025, 2, 25, 8, 30, 0).unwrap();
+        let vars = BTreeMap::from([
+            ("country".to_string(), "CN".to_string()),
+            ("today".to_string(), "overridden".to_string()),
+        ]);
+
+        let cases: &[Case] = &[
+            ("protocol==\"socks5\"", &vars, Some("protocol==\"socks5\"")),
+            ("country=\"{{country}}\"", &vars, Some("country=\"CN\"")),
+            ("after=\"{{ today-7d }}\"", &BTreeMap::new(), Some("after=\"2025-02-18\"")),
+            ("{{today+1w}}", &BTreeMap::new(), Some("2025-03-04")),
+            ("{{now-12h}}", &BTreeMap::new(), Some("2025-02-24 20:30:00")),
+            ("{{today|%Y%m%d}}", &BTreeMap::new(), Some("20250225")),
+            ("{{today-1d|%Y/%m/%d}}", &BTreeMap::new(), Some("2025/02/24")),
+            ("{{today}}", &vars, Some("overridden")),
+            ("{{undefined}}", &vars, None),
+            ("{{today", &vars, None),
+            ("{{today-7}}", &BTreeMap::new(), None),
+            ("{{today*7d}}", &BTreeMap::new(), None),
+            ("{{today-7y}}", &BTreeMap::new(), None),
+            ("{{today|%Q}}", &BTreeMap::new(), None),
+            // 多字节单位、缺少数量与超出范围的偏移
+            ("{{today-7天}}", &BTreeMap::new(), None),
+            ("{{today-天}}", &BTreeMap::new(), None),
+            ("{{today-}}", &BTreeMap::new(), None),
+            ("{{today+d}}", &BTreeMap::new(), None),
+            ("{{today-9223372036854775807w}}", &BTreeMap::new(), None),
+            ("{{now+9999999999h}}", &BTreeMap::new(), None),
+            ("{{today- 7 d}}", &BTreeMap::new(), Some("2025-02-18")),
+        ];
+        for (template, vars, expected) in cases {
+            let result = render(template, vars, now);
+            match expected {
+                Some(expected) => assert_eq!(result.unwrap(), *expected, "{}", template),
+                None => assert!(result.is_err(), "{} -> {:?}", template, result),
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+// 模拟服务收到的请求
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub uri: String, // 路径与查询参数
+    pub headers: hyper::HeaderMap,
+    pub body: String,
+}
+
+impl MockRequest {
+    // 查询参数的值，未解码
+    pub fn query(&self, key: &str) -> Option<String> {
+        let (_, query) = self.uri.split_once('?')?;
+        query.split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.to_string())
+    }
+}
+
+// 测试用的本地HTTP服务，按顺序返回预设的响应，预设响应用完后返回404
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    pub async fn start<S: AsRef<str>>(responses: Vec<(u16, S)>) -> Self {
+        let responses: Arc<Mutex<Vec<(u16, String)>>> = Arc::new(Mutex::new(
+            responses.into_iter().rev().map(|(status, body)| (status, body.as_ref().to_string())).collect()
+        ));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let recorded = Arc::clone(&requests);
+        let make_service = make_service_fn(move |_| {
+            let responses = Arc::clone(&responses);
+            let recorded = Arc::clone(&recorded);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let responses = Arc::clone(&responses);
+                    let recorded = Arc::clone(&recorded);
+                    async move {
+                        let (parts, body) = request.into_parts();
+                        let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+                        recorded.lock().unwrap().push(MockRequest {
+                            uri: parts.uri.to_string(),
+                            headers: parts.headers,
+                            body: String::from_utf8_lossy(&body).to_string(),
+                        });
+                        let (status, body) = responses.lock().unwrap().pop()
+                            .unwrap_or((404, String::new()));
+                        Ok::<_, Infallible>(Response::builder()
+                            .status(StatusCode::from_u16(status).unwrap())
+                            .body(Body::from(body))
+                            .unwrap())
+                    }
+                }))
+            }
+        });
+        let server = Server::from_tcp(listener).unwrap().serve(make_service);
+        tokio::spawn(server);
+
+        MockServer { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
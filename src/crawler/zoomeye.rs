@@ -0,0 +1,191 @@
+use super::paginate::{Page, PageRequest, Paginator};
+use super::request::{send_with_retry, RateLimiter};
+use super::template;
+use super::{Candidate, ProxySource};
+use crate::config::SourceConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::{Engine as _, engine::general_purpose};
+use colored::*;
+use serde::Deserialize;
+use serde_json::Value;
+
+pub const DEFAULT_API_URL: &str = "https://api.zoomeye.ai/v2/search";
+
+const PAGE_SIZE: u64 = 100;
+const DEFAULT_INTERVAL_MS: u64 = 1000;
+// ZoomEye接口成功时的业务状态码
+const SUCCESS_CODE: i64 = 60000;
+
+// ZoomEye API响应结构
+#[derive(Debug, Deserialize)]
+pub struct ZoomEyeResponse {
+    pub code: i64,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub total: u64,
+    #[serde(default)]
+    pub data: Vec<ZoomEyeItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ZoomEyeItem {
+    pub ip: String,
+    pub port: Value, // 端口可能为数字或字符串
+}
+
+// 解析ZoomEye一页结果，返回代理地址与结果总数
+pub fn parse_response(body: &str) -> Result<(Vec<String>, u64)> {
+    let data: ZoomEyeResponse = serde_json::from_str(body)
+        .map_err(|e| anyhow::anyhow!("解析ZoomEye API响应失败: {}", e))?;
+
+    if data.code != SUCCESS_CODE {
+        return Err(anyhow::anyhow!("ZoomEye API返回错误({}): {}", data.code, data.message));
+    }
+
+    let proxies = data.data.iter()
+        .filter_map(|item| {
+            let port = match &item.port {
+                Value::Number(n) => n.as_u64(),
+                Value::String(s) => s.parse::<u64>().ok(),
+                _ => None,
+            }?;
+            Some(format!("{}:{}", item.ip, port))
+        })
+        .collect();
+    Ok((proxies, data.total))
+}
+
+pub struct ZoomEyeSource {
+    config: SourceConfig,
+    limiter: RateLimiter,
+}
+
+impl ZoomEyeSource {
+    pub fn build(config: &SourceConfig) -> Result<Box<dyn ProxySource>> {
+        Ok(Box::new(ZoomEyeSource {
+            config: config.clone(),
+            limiter: RateLimiter::for_source(config, DEFAULT_INTERVAL_MS),
+        }))
+    }
+
+    // size为获取条数，未配置时获取一页
+    fn limit(&self) -> u64 {
+        if self.config.size == 0 { PAGE_SIZE } else { self.config.size }
+    }
+
+    async fn fetch_page(&self, client: &reqwest::Client, query_base64: &str, request: PageRequest) -> Result<Page> {
+        let url = self.config.api_url_or(DEFAULT_API_URL);
+        let request_body = serde_json::json!({
+            "qbase64": query_base64,
+            "page": request.number,
+            "pagesize": PAGE_SIZE,
+            "fields": "ip,port",
+        });
+        let response = send_with_retry("ZoomEye", &self.limiter, self.config.max_retries, || {
+            client.post(url)
+                .header("API-KEY", self.config.key.expose())
+                .json(&request_body)
+        }).await?;
+
+        let status = response.status();
+        let body = response.text()
+            .await
+            .map_err(|e| anyhow::anyhow!("读取ZoomEye API响应失败: {}", e.without_url()))?;
+        if !status.is_success() {
+            return Err(parse_response(&body).err()
+                .unwrap_or_else(|| anyhow::anyhow!("HTTP状态码 {}", status)));
+        }
+
+        let (addresses, total) = parse_response(&body)?;
+        Ok(Page { addresses, size: PAGE_SIZE, total: Some(total), cursor: None })
+    }
+}
+
+#[async_trait]
+impl ProxySource for ZoomEyeSource {
+    fn name(&self) -> &str {
+        self.config.display_name()
+    }
+
+    // ZoomEye按返回的数据条数扣除积分，剩余积分无法通过搜索接口查询
+    fn estimated_cost(&self) -> Option<u64> {
+        Some(self.limit())
+    }
+
+    fn quota_budget(&self) -> u64 {
+        self.config.quota_budget
+    }
+
+    async fn fetch(&self) -> Result<Vec<Candidate>> {
+        println!("{}", "从ZoomEye API获取代理列表...".cyan().bold());
+        let query = template::render_query(&self.config)?;
+
+        let client = reqwest::Client::new();
+        let query_base64 = general_purpose::STANDARD.encode(&query);
+        let limit = self.limit();
+        let paginator = Paginator { name: "ZoomEye", limit, max_pages: limit.div_ceil(PAGE_SIZE) };
+        let addresses = paginator.fetch(|request| self.fetch_page(&client, &query_base64, request)).await?;
+        Ok(addresses.into_iter().map(|address| Candidate::new(address, self.name(), &query)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler::testutil::MockServer;
+
+    const FIXTURE: &str = r#"{
+        "code": 60000,
+        "message": "success",
+        "total": 3,
+        "query": "app=\"socks5\"",
+        "data": [
+            {"ip": "1.2.3.4", "port": 1080},
+            {"ip": "5.6.7.8", "port": "1081"},
+            {"ip": "9.9.9.9", "port": null}
+        ]
+    }"#;
+
+    #[test]
+    fn parses_fixture() {
+        let (proxies, total) = parse_response(FIXTURE).unwrap();
+        assert_eq!(proxies, ["1.2.3.4:1080", "5.6.7.8:1081"]);
+        assert_eq!(total, 3);
+    }
+
+    #[test]
+    fn parses_empty_and_error_responses() {
+        assert_eq!(parse_response(r#"{"code": 60000, "total": 0, "data": []}"#).unwrap(), (vec![], 0));
+
+        let error = parse_response(r#"{"code": 30001, "message": "credits insufficent"}"#).unwrap_err().to_string();
+        assert!(error.contains("30001") && error.contains("credits insufficent"));
+        assert!(parse_response("").is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_api_key_and_page_body() {
+        let server = MockServer::start(vec![
+            (200, r#"{"code": 60000, "total": 1000, "data": [{"ip": "1.2.3.4", "port": 1080}]}"#),
+            (200, r#"{"code": 60000, "total": 1000, "data": [{"ip": "5.6.7.8", "port": 1080}]}"#),
+        ]).await;
+        let source = ZoomEyeSource::build(&SourceConfig {
+            kind: "zoomeye".to_string(),
+            api_url: server.url.clone(),
+            key: "test-key".into(),
+            query_str: "app=\"socks5\"".to_string(),
+            size: 200,
+            rate_limit_ms: 1,
+            max_retries: 0,
+            ..SourceConfig::default()
+        }).unwrap();
+
+        assert_eq!(source.fetch().await.unwrap().len(), 2);
+        let requests = server.requests();
+        assert_eq!(requests[1].headers["API-KEY"], "test-key");
+        let body: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["page"], 2);
+        assert_eq!(body["qbase64"], general_purpose::STANDARD.encode("app=\"socks5\""));
+    }
+}
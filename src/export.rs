@@ -0,0 +1,347 @@
+use crate::proxy_pool::{Anonymity, ProxyEntry, ProxyProtocol};
+use crate::store::{Provenance, ProxyRecord};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::time::Duration;
+
+// 导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Plain,       // 每行一个 host:port
+    Url,         // 每行一个带协议与认证信息的代理URL
+    Json,        // 包含延迟、来源与检测统计的JSON数组
+    Csv,         // 带表头的CSV，可被import重新导入
+    Clash,       // Clash proxy-providers格式的YAML
+    Proxychains, // proxychains.conf
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "plain" | "txt" => Ok(ExportFormat::Plain),
+            "url" => Ok(ExportFormat::Url),
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            "clash" | "yaml" => Ok(ExportFormat::Clash),
+            "proxychains" => Ok(ExportFormat::Proxychains),
+            other => Err(anyhow::anyhow!("不支持的导出格式: {}", other)),
+        }
+    }
+}
+
+// 导出过滤条件，未设置的条件不做过滤
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    pub tags: Vec<String>,              // 含有其中任一标签的代理
+    pub max_latency: Option<Duration>,  // 延迟不超过该值的代理
+    pub anonymity: Option<Anonymity>,   // 匿名度不低于该级别的代理，未判断匿名度的代理会被排除
+}
+
+impl ExportFilter {
+    // 解析 tag=a,b latency=500 anonymity=elite 形式的参数
+    pub fn parse<'a, I: IntoIterator<Item = &'a str>>(args: I) -> Result<Self> {
+        let mut filter = ExportFilter::default();
+        for arg in args {
+            match arg.split_once('=') {
+                Some(("tag", value)) | Some(("tags", value)) => {
+                    filter.tags.extend(value.split(',').filter(|t| !t.is_empty()).map(str::to_string));
+                }
+                Some(("latency", value)) => {
+                    let ms = value.trim_end_matches("ms").parse::<u64>()
+                        .map_err(|_| anyhow::anyhow!("延迟必须为毫秒数: {}", value))?;
+                    filter.max_latency = Some(Duration::from_millis(ms));
+                }
+                Some(("anonymity", value)) => {
+                    filter.anonymity = Some(Anonymity::from_name(value)
+                        .ok_or_else(|| anyhow::anyhow!("匿名度可选 transparent / anonymous / elite: {}", value))?);
+                }
+                _ => return Err(anyhow::anyhow!("无法识别的过滤条件: {}", arg)),
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, entry: &ProxyEntry) -> bool {
+        if !self.tags.is_empty() && !entry.tags.iter().any(|tag| self.tags.contains(tag)) {
+            return false;
+        }
+        if self.max_latency.is_some_and(|max| entry.latency > max) {
+            return false;
+        }
+        match self.anonymity {
+            Some(min) => entry.anonymity.is_some_and(|anonymity| anonymity >= min),
+            None => true,
+        }
+    }
+}
+
+// JSON导出中的单条记录
+#[derive(Serialize)]
+struct ExportRecord<'a> {
+    address: &'a str,
+    protocol: ProxyProtocol,
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<&'a str>,          // 认证信息单独输出，便于import重新导入
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<&'a str>,
+    latency_ms: u64,
+    fail_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    anonymity: Option<Anonymity>,
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provenance: Option<&'a Provenance>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_seen: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_alive: Option<u64>,
+    checks: usize,                      // 数据库中保留的检测次数
+    successes: usize,                   // 其中检测通过的次数
+}
+
+// 拆分 host:port，IPv6地址去掉方括号
+fn split_address(address: &str) -> (&str, &str) {
+    let (host, port) = address.rsplit_once(':').unwrap_or((address, ""));
+    (host.trim_start_matches('[').trim_end_matches(']'), port)
+}
+
+// 按格式渲染代理列表，records为代理数据库中对应的记录，用于JSON导出的检测统计
+pub fn render(entries: &[ProxyEntry], records: &[Option<ProxyRecord>], format: ExportFormat) -> Result<String> {
+    let mut output = match format {
+        ExportFormat::Plain => entries.iter()
+            .map(|entry| entry.address.clone())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ExportFormat::Url => entries.iter()
+            .map(ProxyEntry::url)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ExportFormat::Json => render_json(entries, records)?,
+        ExportFormat::Csv => render_csv(entries),
+        ExportFormat::Clash => render_clash(entries)?,
+        ExportFormat::Proxychains => render_proxychains(entries),
+    };
+    if !output.ends_with('\n') {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+fn render_json(entries: &[ProxyEntry], records: &[Option<ProxyRecord>]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&export_records(entries, records))?)
+}
+
+// 代理列表的JSON值，与JSON导出的格式相同，用于管理API
+pub fn json_value(entries: &[ProxyEntry], records: &[Option<ProxyRecord>]) -> Result<serde_json::Value> {
+    Ok(serde_json::to_value(export_records(entries, records))?)
+}
+
+fn export_records<'a>(entries: &'a [ProxyEntry], records: &[Option<ProxyRecord>]) -> Vec<ExportRecord<'a>> {
+    entries.iter()
+        .zip(records.iter().chain(std::iter::repeat(&None)))
+        .map(|(entry, record)| ExportRecord {
+            address: &entry.address,
+            protocol: entry.protocol,
+            url: entry.url(),
+            username: entry.credentials.as_ref().map(|c| c.username.as_str()),
+            password: entry.credentials.as_ref().map(|c| c.password.as_str()),
+            latency_ms: entry.latency.as_millis() as u64,
+            fail_count: entry.fail_count,
+            anonymity: entry.anonymity,
+            tags: &entry.tags,
+            provenance: entry.provenance.as_ref(),
+            first_seen: record.as_ref().map(|r| r.first_seen),
+            last_alive: record.as_ref().and_then(|r| r.last_alive),
+            checks: record.as_ref().map_or(0, |r| r.history.len()),
+            successes: record.as_ref().map_or(0, |r| r.history.iter().filter(|c| c.latency_ms.is_some()).count()),
+        })
+        .collect()
+}
+
+// 包含逗号、引号或换行的字段用双引号包裹
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn render_csv(entries: &[ProxyEntry]) -> String {
+    let mut lines = vec!["host,port,protocol,username,password,latency_ms,anonymity,tags,source".to_string()];
+    for entry in entries {
+        let (host, port) = split_address(&entry.address);
+        let (username, password) = entry.credentials.as_ref()
+            .map(|c| (c.username.as_str(), c.password.as_str()))
+            .unwrap_or_default();
+        let fields = [
+            host.to_string(),
+            port.to_string(),
+            entry.protocol.scheme().to_string(),
+            username.to_string(),
+            password.to_string(),
+            entry.latency.as_millis().to_string(),
+            entry.anonymity.map(|a| a.name().to_string()).unwrap_or_default(),
+            entry.tags.join(";"),
+            entry.provenance.as_ref().map(|p| p.source.clone()).unwrap_or_default(),
+        ];
+        lines.push(fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+    }
+    lines.join("\n")
+}
+
+#[derive(Serialize)]
+struct ClashProxy<'a> {
+    name: String,
+    #[serde(rename = "type")]
+    kind: &'a str,
+    server: &'a str,
+    port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct ClashProvider<'a> {
+    proxies: Vec<ClashProxy<'a>>,
+}
+
+// Clash proxy-providers文件，代理名称优先使用第一个标签，重名时使用地址
+fn render_clash(entries: &[ProxyEntry]) -> Result<String> {
+    let mut names = HashSet::new();
+    let mut proxies = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let (server, port) = split_address(&entry.address);
+        let port = match port.parse::<u16>() {
+            Ok(port) => port,
+            Err(_) => continue,
+        };
+        let name = match entry.tags.first() {
+            Some(tag) if !names.contains(tag) => tag.clone(),
+            _ => entry.address.clone(),
+        };
+        names.insert(name.clone());
+        proxies.push(ClashProxy {
+            name,
+            kind: entry.protocol.scheme(),
+            server,
+            port,
+            username: entry.credentials.as_ref().map(|c| c.username.as_str()),
+            password: entry.credentials.as_ref().map(|c| c.password.as_str()),
+        });
+    }
+    Ok(serde_yaml::to_string(&ClashProvider { proxies })?)
+}
+
+// proxychains配置，使用dynamic_chain跳过不可用的代理
+fn render_proxychains(entries: &[ProxyEntry]) -> String {
+    let mut lines = vec![
+        "# 由LokiPool导出".to_string(),
+        "dynamic_chain".to_string(),
+        "proxy_dns".to_string(),
+        "tcp_read_time_out 15000".to_string(),
+        "tcp_connect_time_out 8000".to_string(),
+        String::new(),
+        "[ProxyList]".to_string(),
+    ];
+    for entry in entries {
+        let (host, port) = split_address(&entry.address);
+        let mut line = format!("{} {} {}", entry.protocol.scheme(), host, port);
+        if let Some(credentials) = &entry.credentials {
+            line.push_str(&format!(" {} {}", credentials.username, credentials.password));
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::importer::{self, ImportFormat};
+    use crate::proxy_pool::Credentials;
+
+    fn entry(address: &str, tags: &[&str], credentials: Option<(&str, &str)>) -> ProxyEntry {
+        let mut entry = ProxyEntry::new(address.to_string(), None);
+        entry.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        entry.credentials = credentials.map(|(username, password)| Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        });
+        entry
+    }
+
+    fn entries() -> Vec<ProxyEntry> {
+        let mut http = entry("5.6.7.8:8080", &["hk", "a,b"], Some(("us\"er", "p,ss")));
+        http.protocol = ProxyProtocol::Http;
+        vec![entry("1.2.3.4:1080", &[], None), http, entry("[2001:db8::1]:1080", &["hk"], None)]
+    }
+
+    // 代理的地址、协议、认证信息与标签
+    type Summary = (String, ProxyProtocol, Option<Credentials>, Vec<String>);
+
+    fn summary(entries: &[ProxyEntry]) -> Vec<Summary> {
+        entries.iter()
+            .map(|e| (e.address.clone(), e.protocol, e.credentials.clone(), e.tags.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn csv_and_json_round_trip_through_import() {
+        let entries = entries();
+        for (format, import_format) in [(ExportFormat::Csv, ImportFormat::Csv), (ExportFormat::Json, ImportFormat::Json)] {
+            let output = render(&entries, &[], format).unwrap();
+            let imported = importer::parse(&output, import_format, "").unwrap();
+            assert_eq!(imported.skipped, 0, "{:?}", format);
+            assert_eq!(summary(&imported.entries), summary(&entries), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+
+        let output = render(&entries()[1..2], &[], ExportFormat::Csv).unwrap();
+        assert_eq!(output.lines().nth(1), Some("5.6.7.8,8080,http,\"us\"\"er\",\"p,ss\",0,,\"hk;a,b\","));
+    }
+
+    #[test]
+    fn clash_names_fall_back_to_address_on_duplicates() {
+        let output = render(&entries(), &[], ExportFormat::Clash).unwrap();
+        let provider: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        let proxies = provider["proxies"].as_sequence().unwrap();
+        let names: Vec<&str> = proxies.iter().map(|p| p["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["1.2.3.4:1080", "hk", "[2001:db8::1]:1080"]);
+        assert_eq!(proxies[2]["server"].as_str(), Some("2001:db8::1"));
+        assert_eq!(proxies[1]["username"].as_str(), Some("us\"er"));
+        assert!(proxies[0].get("username").is_none());
+    }
+
+    #[test]
+    fn renders_proxychains() {
+        let output = render(&entries(), &[], ExportFormat::Proxychains).unwrap();
+        let list: Vec<&str> = output.lines().skip_while(|line| *line != "[ProxyList]").skip(1).collect();
+        assert_eq!(list, ["socks5 1.2.3.4 1080", "http 5.6.7.8 8080 us\"er p,ss", "socks5 2001:db8::1 1080"]);
+        assert!(output.contains("dynamic_chain\n") && output.ends_with('\n'));
+    }
+
+    #[test]
+    fn parses_filters() {
+        let filter = ExportFilter::parse(["tag=hk,,us", "tags=team", "latency=500ms", "anonymity=Elite"]).unwrap();
+        assert_eq!(filter.tags, ["hk", "us", "team"]);
+        assert_eq!(filter.max_latency, Some(Duration::from_millis(500)));
+        assert_eq!(filter.anonymity, Some(Anonymity::Elite));
+
+        let invalid = ["latency=fast", "latency=-1", "anonymity=high", "country=hk", "tag"];
+        for arg in invalid {
+            assert!(ExportFilter::parse([arg]).is_err(), "{}", arg);
+        }
+    }
+}
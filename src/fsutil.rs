@@ -0,0 +1,91 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// 进程内每次写入递增，避免同时写入同一文件的写入者使用相同的临时文件
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// 临时文件与目标文件位于同一目录，保证rename是原子操作
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let seq = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.tmp.{}.{}", file_name, std::process::id(), seq))
+}
+
+// 原子写入文件：先写入临时文件并fsync，再rename覆盖目标文件
+// 写入过程中崩溃时，目标文件要么是旧内容，要么是完整的新内容
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let tmp = temp_path(path);
+
+    let result = (|| {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+        return result;
+    }
+
+    // 同步目录项，确保rename本身落盘
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_paths_are_unique_per_call() {
+        let path = Path::new("dir/proxies.txt");
+        let first = temp_path(path);
+        let second = temp_path(path);
+        assert_ne!(first, second);
+        assert_eq!(first.parent(), path.parent());
+    }
+
+    #[test]
+    fn concurrent_writes_leave_a_complete_file() {
+        let dir = std::env::temp_dir().join(format!("lokipool-fsutil-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.txt");
+
+        let writers: Vec<_> = (0..8).map(|i| {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let content = vec![b'a' + i as u8; 64 * 1024];
+                for _ in 0..20 {
+                    write_atomic(&path, &content).unwrap();
+                }
+            })
+        }).collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let content = fs::read(&path).unwrap();
+        assert_eq!(content.len(), 64 * 1024);
+        assert!(content.iter().all(|b| *b == content[0]));
+        let leftovers = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(leftovers, 1);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::metrics::FailureReason;
+use crate::socks_server::{relay, AuthError, Connection, Session};
+use anyhow::Result;
+use base64::{Engine as _, engine::general_purpose};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+// 请求头的最大长度
+const MAX_HEADER_SIZE: usize = 8192;
+
+// 转发普通HTTP请求时去掉的逐跳请求头
+const HOP_HEADERS: &[&str] = &["proxy-authorization", "proxy-connection", "connection", "keep-alive"];
+
+struct Request {
+    method: String,
+    target: String,
+    version: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// 处理HTTP代理连接：CONNECT请求建立隧道，其余请求改写为普通请求后转发
+pub(crate) async fn handle(client: TcpStream, connection: &Connection) -> Result<()> {
+    let config = &connection.config;
+    let (reader, mut writer) = client.into_split();
+    // 请求头之后已被读入缓冲区的数据在转发时会先写给上游
+    let mut reader = BufReader::new(reader);
+
+    let request = match read_request(&mut reader).await {
+        Ok(request) => request,
+        Err(e) => {
+            connection.record_failure(FailureReason::BadRequest);
+            respond(&mut writer, "400 Bad Request").await?;
+            return Err(e);
+        }
+    };
+
+    let session = if connection.requires_auth() {
+        match authenticate(&request, connection).await {
+            Ok(session) => session,
+            Err(AuthError::ConnectionLimit) => {
+                connection.record_failure(FailureReason::ConnectionLimit);
+                respond(&mut writer, "429 Too Many Requests").await?;
+                return Err(AuthError::ConnectionLimit.into());
+            }
+            Err(e) => {
+                connection.record_failure(e.reason());
+                writer.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"LokiPool\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await?;
+                return Err(e.into());
+            }
+        }
+    } else {
+        Session::default()
+    };
+
+    let is_connect = request.method.eq_ignore_ascii_case("CONNECT");
+    let (host, port) = match target_address(&request, is_connect) {
+        Some(address) => address,
+        None => {
+            connection.record_failure(FailureReason::BadRequest);
+            respond(&mut writer, "400 Bad Request").await?;
+            return Err(anyhow::anyhow!("无法解析的请求地址: {}", request.target));
+        }
+    };
+
+    if let Err(e) = connection.check_destination(&session, &host, port) {
+        respond(&mut writer, "403 Forbidden").await?;
+        return Err(e);
+    }
+    let meter = match connection.meter(&session) {
+        Ok(meter) => meter,
+        Err(e) => {
+            respond(&mut writer, "429 Too Many Requests").await?;
+            return Err(e);
+        }
+    };
+
+    let proxy = match connection.select_proxy(&session).await {
+        Some(proxy) => proxy,
+        None => {
+            connection.record_failure(FailureReason::NoProxy);
+            respond(&mut writer, "503 Service Unavailable").await?;
+            if config.log.show_error_log {
+                eprintln!("没有可用的代理");
+            }
+            return Ok(());
+        }
+    };
+    let mut upstream = match connection.connect_upstream(&proxy, &host, port).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            if config.log.show_error_log {
+                eprintln!("代理连接失败: {} - {}", proxy.address, e);
+            }
+            respond(&mut writer, "502 Bad Gateway").await?;
+            return Ok(());
+        }
+    };
+
+    if is_connect {
+        writer.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n").await?;
+    } else {
+        // 连接只用于这一个请求，避免客户端在同一连接上发往其他主机的请求被转发到这里
+        upstream.write_all(origin_request(&request).as_bytes()).await?;
+    }
+    relay(reader, writer, upstream, config, &meter).await;
+    Ok(())
+}
+
+async fn respond<W: AsyncWriteExt + Unpin>(writer: &mut W, status: &str) -> Result<()> {
+    let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+    writer.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+async fn read_request<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> Result<Request> {
+    let mut lines = Vec::new();
+    let mut size = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(anyhow::anyhow!("客户端在请求头结束前关闭了连接"));
+        }
+        size += line.len();
+        if size > MAX_HEADER_SIZE {
+            return Err(anyhow::anyhow!("请求头过长"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']).to_string();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+
+    let mut request_line = lines.first()
+        .ok_or_else(|| anyhow::anyhow!("缺少请求行"))?
+        .split_whitespace();
+    let (method, target, version) = match (request_line.next(), request_line.next(), request_line.next()) {
+        (Some(method), Some(target), Some(version)) => (method, target, version),
+        _ => return Err(anyhow::anyhow!("无法解析的请求行: {}", lines[0])),
+    };
+    let headers = lines[1..].iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+    Ok(Request {
+        method: method.to_string(),
+        target: target.to_string(),
+        version: version.to_string(),
+        headers,
+    })
+}
+
+async fn authenticate(request: &Request, connection: &Connection) -> Result<Session, AuthError> {
+    let credentials = request.header("Proxy-Authorization")
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|token| general_purpose::STANDARD.decode(token.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok());
+    match credentials.as_deref().and_then(|c| c.split_once(':')) {
+        Some((username, password)) => connection.authenticate(username, password).await,
+        None => Err(AuthError::InvalidCredentials),
+    }
+}
+
+// CONNECT请求的目标为 host:port，普通请求的目标为 http:// 开头的完整地址
+fn target_address(request: &Request, is_connect: bool) -> Option<(String, u16)> {
+    if is_connect {
+        let (host, port) = request.target.rsplit_once(':')?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        return Some((host.to_string(), port.parse().ok()?));
+    }
+    let url = reqwest::Url::parse(&request.target).ok()?;
+    if url.scheme() != "http" {
+        return None;
+    }
+    let host = url.host_str()?.trim_start_matches('[').trim_end_matches(']').to_string();
+    Some((host, url.port_or_known_default()?))
+}
+
+// 把代理请求改写为发往目标服务器的请求
+fn origin_request(request: &Request) -> String {
+    let path = reqwest::Url::parse(&request.target)
+        .map(|url| match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        })
+        .unwrap_or_else(|_| "/".to_string());
+    let mut output = format!("{} {} {}\r\n", request.method, path, request.version);
+    for (key, value) in &request.headers {
+        if !HOP_HEADERS.contains(&key.to_ascii_lowercase().as_str()) {
+            output.push_str(&format!("{}: {}\r\n", key, value));
+        }
+    }
+    output.push_str("Connection: close\r\n\r\n");
+    output
+}
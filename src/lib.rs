@@ -2,7 +2,19 @@ pub mod config;
 pub mod proxy_pool;
 pub mod socks_server;
 pub mod crawler;
+pub mod store;
+pub mod fsutil;
+pub mod importer;
+pub mod export;
+pub mod reload;
+pub mod upstream;
+pub mod http_proxy;
+pub mod ratelimit;
+pub mod users;
+pub mod usage;
+pub mod admin;
+pub mod metrics;
 
 pub use proxy_pool::ProxyPool;
 pub use socks_server::SocksServer;
-pub use config::Config; 
\ No newline at end of file
+pub use config::{Config, ConfigHandle};
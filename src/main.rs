@@ -1,12 +1,17 @@
 use tokio::io::{self, AsyncBufReadExt, BufReader};
 use std::io::Write;
 use anyhow::Result;
-use lokipool::{Config, SocksServer};
+use lokipool::{Config, ConfigHandle, ProxyPool, SocksServer};
+use lokipool::config::ConfigOverrides;
+use lokipool::proxy_pool::ProxyEntry;
+use clap::{Parser, Subcommand};
 use tokio::signal;
 use colored::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::fs;
 use std::fs::File;
+use std::sync::Arc;
 
 const LOGO: &str = r#"
 ██╗      ██████╗ ██╗  ██╗██╗██████╗  ██████╗  ██████╗ ██╗     
@@ -19,32 +24,303 @@ const LOGO: &str = r#"
 
 const VERSION: &str = "v0.1.5";
 
+#[derive(Parser)]
+#[command(name = "lokipool", version = VERSION, about = "A Fast and Reliable SOCKS5 Proxy Pool")]
+struct Cli {
+    /// 配置文件路径，不存在时生成默认配置
+    #[arg(short, long, global = true, default_value = "config.toml")]
+    config: PathBuf,
+
+    /// 覆盖监听地址，格式为 host:port
+    #[arg(long, global = true)]
+    bind: Option<String>,
+
+    /// 覆盖代理文件路径
+    #[arg(long, global = true)]
+    proxy_file: Option<String>,
+
+    /// 不显示Logo与版本信息
+    #[arg(long, global = true)]
+    no_banner: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 启动代理服务(默认)
+    Serve {
+        /// 不读取标准输入的交互命令，适合systemd等后台运行
+        #[arg(long)]
+        no_repl: bool,
+    },
+    /// 测试代理文件中的代理并输出结果，不启动代理服务
+    Check {
+        /// 代理文件，支持代理文件、Clash、订阅、CSV与JSON格式
+        file: PathBuf,
+        /// 文件格式: text / json / clash / subscription / csv，省略时自动识别
+        #[arg(long, default_value = "")]
+        format: String,
+        /// 将可用代理写入该文件
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// 只从配置的代理源爬取代理，结果合并到代理文件或暂存文件
+    Fetch {
+        /// 只输出将要新增的代理，不写入任何文件
+        #[arg(long)]
+        dry_run: bool,
+        /// 爬取结果写入的暂存文件
+        #[arg(long)]
+        staging_file: Option<String>,
+    },
+    /// 导出代理数据库中最近检测可用的代理
+    Export {
+        /// 导出格式: plain / url / json / csv / clash / proxychains
+        format: String,
+        /// 输出文件
+        output: PathBuf,
+        /// 过滤条件，例如 tag=hk latency=500 anonymity=elite
+        filters: Vec<String>,
+        /// 导出前重新测试代理文件中的代理
+        #[arg(long)]
+        recheck: bool,
+    },
+    /// 生成用户文件中使用的argon2密码哈希
+    HashPassword {
+        /// 密码，省略时从标准输入读取一行
+        password: Option<String>,
+    },
+    /// 配置文件相关操作
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// 检查配置文件，列出所有问题，不会生成缺失的配置文件
+    Check,
+}
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
     // 初始化日志
     tracing_subscriber::fmt::init();
 
-    // 显示Logo和版本信息
-    println!("{}", LOGO.bright_cyan());
-    println!("{}", "A Fast and Reliable SOCKS5 Proxy Pool".bright_black());
-    println!("{} {}", "Version:".bright_black(), VERSION.bright_black());
-    println!("{} {}", "Author:".bright_black(), "Le1a".bright_black());
-    println!("{} {}\n", "GitHub:".bright_black(), "https://github.com/Le1a/LokiPool".bright_blue().underline());
+    let cli = Cli::parse();
+
+    if !cli.no_banner {
+        // 显示Logo和版本信息
+        println!("{}", LOGO.bright_cyan());
+        println!("{}", "A Fast and Reliable SOCKS5 Proxy Pool".bright_black());
+        println!("{} {}", "Version:".bright_black(), VERSION.bright_black());
+        println!("{} {}", "Author:".bright_black(), "Le1a".bright_black());
+        println!("{} {}\n", "GitHub:".bright_black(), "https://github.com/Le1a/LokiPool".bright_blue().underline());
+    }
 
-    // 加载配置
-    let config = match Config::load() {
-        Ok(cfg) => {
+    // 加载配置，命令行覆盖在重载配置时同样生效
+    let overrides = ConfigOverrides {
+        bind: cli.bind.clone(),
+        proxy_file: cli.proxy_file.clone(),
+    };
+    if let Some(Command::Config { command: ConfigCommand::Check }) = &cli.command {
+        if let Err(e) = check_config(&cli.config, &overrides) {
+            eprintln!("{} {}", "错误:".red().bold(), e);
+            process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::HashPassword { password }) = &cli.command {
+        match hash_password(password.clone()) {
+            Ok(hash) => println!("{}", hash),
+            Err(e) => {
+                eprintln!("{} {}", "错误:".red().bold(), e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    let handle = match ConfigHandle::load(&cli.config, overrides) {
+        Ok(handle) => {
             println!("{}", "成功加载配置文件".green().bold());
-            cfg
+            handle
         }
         Err(e) => {
             eprintln!("{} {}", "加载配置文件失败:".red().bold(), e);
-            return Ok(());
+            process::exit(1);
+        }
+    };
+    let config = (*handle.get()).clone();
+
+    let result = match cli.command.unwrap_or(Command::Serve { no_repl: false }) {
+        Command::Serve { no_repl } => serve(handle, no_repl).await,
+        Command::Check { file, format, output } => check(config, &file, &format, output.as_deref()).await,
+        Command::Fetch { dry_run, staging_file } => fetch(config, dry_run, staging_file).await,
+        Command::Export { format, output, filters, recheck } => export(config, &format, &output, &filters, recheck).await,
+        Command::Config { .. } | Command::HashPassword { .. } => unreachable!("该子命令在加载配置前处理"),
+    };
+    if let Err(e) = result {
+        eprintln!("{} {}", "错误:".red().bold(), e);
+        process::exit(1);
+    }
+}
+
+// 检查配置文件并列出全部问题，存在问题时返回错误
+fn check_config(path: &Path, overrides: &ConfigOverrides) -> Result<()> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("读取配置文件失败: {} - {}", path.display(), e))?;
+    let (mut config, unknown) = Config::parse(&content)?;
+    lokipool::config::warn_unknown_keys(&unknown);
+    overrides.apply(&mut config)?;
+
+    let issues = config.validate();
+    for issue in &issues {
+        println!("  {} {}", format!("{}:", issue.key).red().bold(), issue.message);
+    }
+    let mut problems = issues.len();
+    if !config.proxy.users_file.is_empty() {
+        match lokipool::users::check_file(&config.proxy.users_file) {
+            Ok(count) => println!("{} {}", "用户文件检查通过，用户数:".green().bold(), count.to_string().yellow()),
+            Err(e) => {
+                println!("  {} {}", "proxy.users_file:".red().bold(), e);
+                problems += 1;
+            }
+        }
+    }
+    if problems > 0 {
+        return Err(anyhow::anyhow!("配置检查发现{}个问题", problems));
+    }
+    let sources = config.source_configs();
+    println!("{} {}",
+        "配置检查通过，已启用的代理源:".green().bold(),
+        format!("{}/{}", sources.iter().filter(|s| s.enabled).count(), sources.len()).yellow()
+    );
+    Ok(())
+}
+
+fn hash_password(password: Option<String>) -> Result<String> {
+    let password = match password {
+        Some(password) => password,
+        None => {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
         }
     };
+    if password.is_empty() {
+        return Err(anyhow::anyhow!("密码不能为空"));
+    }
+    lokipool::users::hash_password(&password)
+}
+
+// 测试代理文件并输出结果，不修改代理数据库
+async fn check(mut config: Config, file: &Path, format: &str, output: Option<&Path>) -> Result<()> {
+    let format = lokipool::importer::ImportFormat::from_name(format)?;
+    let result = lokipool::importer::load_file(file, format)?;
+    if result.skipped > 0 {
+        println!("{} {}", "跳过不支持或无法识别的条目:".yellow().bold(), result.skipped.to_string().yellow());
+    }
+    if result.entries.is_empty() {
+        return Err(anyhow::anyhow!("文件中没有可测试的代理"));
+    }
+
+    config.proxy.db_file.clear();
+    let pool = ProxyPool::new(config.clone());
+    let total = result.entries.len();
+    let valid = pool.test_proxies(
+        result.entries,
+        "代理测试",
+        config.proxy.test_timeout,
+        false,
+        true,
+        |entry: ProxyEntry| (entry.address.clone(), Some(entry))
+    ).await;
+
+    println!();
+    print_proxies(&valid);
+    println!("{} {} / {}",
+        "可用代理:".green().bold(),
+        valid.len().to_string().yellow().bold(),
+        total
+    );
+
+    if let Some(output) = output {
+        pool.save_proxy_file(output, &valid).await?;
+        println!("{} {}", "可用代理已保存到:".green().bold(), output.display());
+    }
+    if valid.is_empty() {
+        return Err(anyhow::anyhow!("没有可用的代理"));
+    }
+    Ok(())
+}
+
+// 只爬取代理，不启动代理服务
+async fn fetch(mut config: Config, dry_run: bool, staging_file: Option<String>) -> Result<()> {
+    config.crawler.dry_run |= dry_run;
+    if let Some(staging_file) = staging_file {
+        config.crawler.staging_file = staging_file;
+    }
+    if !config.has_enabled_sources() {
+        return Err(anyhow::anyhow!("没有启用任何代理源"));
+    }
+    lokipool::crawler::fetch_proxies(&config, None).await?;
+    Ok(())
+}
+
+// 导出代理数据库中最近一次检测可用的代理
+// --recheck时在临时的代理池中重新测试代理文件，与check一样不修改代理文件与代理数据库
+async fn export(mut config: Config, format: &str, output: &Path, filters: &[String], recheck: bool) -> Result<()> {
+    let format = lokipool::export::ExportFormat::from_name(format)?;
+    let filter = lokipool::export::ExportFilter::parse(filters.iter().map(String::as_str))?;
+
+    if recheck {
+        config.proxy.db_file.clear();
+    }
+    let pool = ProxyPool::new(config.clone());
+    if recheck {
+        pool.recheck_file(&config.proxy.proxy_file).await?;
+    } else if pool.restore_from_store().await == 0 {
+        return Err(anyhow::anyhow!("代理数据库中没有代理文件中代理的可用记录，可使用 --recheck 重新测试代理文件"));
+    }
+
+    let count = pool.export(output, format, &filter).await?;
+    println!("{} {} {} {}",
+        "已导出代理:".green().bold(),
+        count.to_string().yellow().bold(),
+        "个 ->".green().bold(),
+        output.display()
+    );
+    Ok(())
+}
+
+fn print_proxies(proxies: &[ProxyEntry]) {
+    for (i, proxy) in proxies.iter().enumerate() {
+        let latency = proxy.latency.as_millis();
+        let latency_str = match latency {
+            0..=100 => latency.to_string().green(),
+            101..=300 => latency.to_string().yellow(),
+            _ => latency.to_string().red(),
+        };
+        let source = proxy.provenance.as_ref()
+            .map(|p| format!(" [{}]", p.source))
+            .unwrap_or_default();
+        println!("{:3}. {} - {}ms{}", 
+            (i + 1).to_string().blue().bold(),
+            proxy.address.cyan(),
+            latency_str,
+            source.bright_black()
+        );
+    }
+}
+
+async fn serve(handle: ConfigHandle, no_repl: bool) -> Result<()> {
+    let config = handle.get();
 
     // 创建SOCKS5服务器
-    let server = SocksServer::new(config.clone());
+    let server = SocksServer::with_config(handle.clone());
     println!("\n{}", "创建SOCKS5服务器...".cyan().bold());
     
     // 显示自动切换配置
@@ -62,51 +338,71 @@ async fn main() -> Result<()> {
         println!("{} {}", "代理文件不存在，正在创建:".yellow().bold(), &proxy_file);
         match File::create(&proxy_file) {
             Ok(_) => println!("{}", "创建代理文件成功".green().bold()),
-            Err(e) => {
-                eprintln!("{} {}", "创建代理文件失败:".red().bold(), e);
-                return Ok(());
-            }
+            Err(e) => return Err(anyhow::anyhow!("创建代理文件失败: {}", e)),
         }
     }
     
-    // 检查代理文件是否为空
+    // 加载用户文件，之后修改用户文件会在新的连接认证时自动重新加载
+    if !config.proxy.users_file.is_empty() {
+        let count = server.users().load(&config.proxy.users_file)?;
+        println!("{} {}", "已加载用户文件，用户数:".green().bold(), count.to_string().yellow());
+    }
+
+    // 检查代理文件是否为空，配置了导入文件时不视为空
     let is_empty = match fs::metadata(&proxy_file) {
-        Ok(metadata) => metadata.len() == 0,
-        Err(e) => {
-            eprintln!("{} {}", "读取代理文件失败:".red().bold(), e);
-            return Ok(());
-        }
+        Ok(metadata) => metadata.len() == 0 && config.proxy.import_files.is_empty(),
+        Err(e) => return Err(anyhow::anyhow!("读取代理文件失败: {}", e)),
     };
     
+    // 从数据库恢复上次检测可用的代理，避免每次启动都等待全量测试
+    let restored = server.get_proxy_pool().restore_from_store().await;
+    if restored > 0 {
+        println!("{} {} {}", 
+            "从代理数据库恢复可用代理:".green().bold(),
+            restored.to_string().yellow().bold(),
+            "个".green().bold()
+        );
+    }
+    
     // 如果文件为空，从配置的源获取代理
     if is_empty {
         println!("{}", "代理文件为空".yellow().bold());
         
         // 检查是否有任何代理源开启
-        if config.fofa.switch || config.quake.switch || config.hunter.switch {
+        if config.has_enabled_sources() {
             println!("{}", "尝试从配置的API获取代理...".cyan().bold());
-            match lokipool::crawler::fetch_proxies(&config).await {
+            match lokipool::crawler::fetch_proxies(&config, Some(server.get_proxy_pool())).await {
                 Ok(_) => println!("{}", "从API获取代理成功".green().bold()),
-                Err(e) => {
-                    eprintln!("{} {}", "从API获取代理失败:".red().bold(), e);
-                    return Ok(());
-                }
+                Err(e) => return Err(anyhow::anyhow!("从API获取代理失败: {}", e)),
+            }
+
+            // dry_run或写入暂存文件时代理文件不会变化
+            if config.crawler.dry_run || !config.crawler.staging_file.is_empty() {
+                println!("{}", "爬取结果未写入代理文件，审核后请将代理合并到代理文件再启动".yellow().bold());
+                return Ok(());
             }
         } else {
-            eprintln!("{}", "代理文件内容为空且自动爬取功能未配置".red().bold());
-            return Ok(());
+            return Err(anyhow::anyhow!("代理文件内容为空且自动爬取功能未配置"));
         }
     }
     
-    // 加载代理列表
-    if let Err(e) = server.get_proxy_pool().load_from_file(&proxy_file).await {
-        eprintln!("{} {}", "加载代理列表失败:".red().bold(), e);
-        return Ok(());
+    // 加载代理列表，已从数据库恢复时在后台重新测试
+    if restored > 0 {
+        println!("{}", "将在后台重新测试代理列表".cyan().bold());
+        let proxy_pool = Arc::clone(server.get_proxy_pool());
+        let proxy_file = proxy_file.clone();
+        tokio::spawn(async move {
+            if let Err(e) = proxy_pool.load_from_file(&proxy_file).await {
+                eprintln!("{} {}", "加载代理列表失败:".red().bold(), e);
+            }
+        });
+    } else if let Err(e) = server.get_proxy_pool().load_from_file(&proxy_file).await {
+        return Err(anyhow::anyhow!("加载代理列表失败: {}", e));
     }
     
-    // 根据配置决定是否启动健康检查任务
+    // 启动健康检查任务，health_check_switch关闭时任务空转，重载配置开启后生效
+    server.get_proxy_pool().start_health_check();
     if config.proxy.health_check_switch {
-        server.get_proxy_pool().start_health_check();
         println!("{} {}", 
             "健康检查已启用，间隔:".green().bold(),
             config.proxy.health_check_interval.to_string().yellow().bold()
@@ -114,16 +410,50 @@ async fn main() -> Result<()> {
     } else {
         println!("{}", "健康检查已禁用".yellow().bold());
     }
+
+    // 监视配置文件变化与SIGHUP，热重载配置
+    lokipool::reload::start_config_watch(handle.clone());
+    
+    // 启动后台定时爬取任务
+    let crawl_tasks = lokipool::crawler::start_scheduled_crawl(Arc::clone(server.get_proxy_pool()));
+    if crawl_tasks > 0 {
+        println!("{} {}", 
+            "定时爬取已启用，代理源数:".green().bold(),
+            crawl_tasks.to_string().yellow().bold()
+        );
+    }
+    
+    // 启动水位线补充任务，重载配置后开启min_pool_size同样生效；检查初始代理池是否低于水位线
+    lokipool::crawler::start_refill_listener(Arc::clone(server.get_proxy_pool()));
+    if config.proxy.min_pool_size > 0 {
+        if !config.has_enabled_sources() {
+            println!("{}", "未启用任何代理源，低于水位线时只能复测隔离区".yellow().bold());
+        }
+        server.get_proxy_pool().check_watermark().await;
+    }
     
+    // 启动HTTP管理API
+    if config.admin.enabled {
+        let addr = lokipool::admin::start(server.clone()).await?;
+        println!("{} {}", "管理API已启动在".green().bold(), addr);
+    }
+
     // 创建用户输入处理任务
+    let usage = Arc::clone(server.usage());
     let server_clone = server.clone();
     let input_handle = tokio::spawn(async move {
-        let (host, port) = server_clone.get_bind_info();
-        println!("\n{} {}:{}", 
-            "代理服务器已启动在".green().bold(),
-            host,
-            port
-        );
+        println!();
+        for listener in server_clone.listeners() {
+            println!("{} {} {}",
+                format!("{}代理服务器已启动在", listener.protocol.name()).green().bold(),
+                listener.bind_addr(),
+                listener.name.bright_black()
+            );
+        }
+        // 后台运行时不读取标准输入，直到收到终止信号
+        if no_repl {
+            std::future::pending::<()>().await;
+        }
         help().await;
         print!("> ");
         
@@ -133,24 +463,16 @@ async fn main() -> Result<()> {
         let mut lines = reader.lines();
 
         while let Ok(Some(line)) = lines.next_line().await {
-            match line.trim().split_whitespace().next().unwrap_or("") {
+            match line.split_whitespace().next().unwrap_or("") {
                 "help" => {
                     help().await;
                 }
                 "list" => {
                     println!("\n当前代理列表:");
-                    for (i, proxy) in server_clone.get_proxy_pool().list_proxies().await.iter().enumerate() {
-                        let latency = proxy.latency.as_millis();
-                        let latency_str = match latency {
-                            0..=100 => latency.to_string().green(),
-                            101..=300 => latency.to_string().yellow(),
-                            _ => latency.to_string().red(),
-                        };
-                        println!("{:3}. {} - {}ms", 
-                            (i + 1).to_string().blue().bold(),
-                            proxy.address.cyan(),
-                            latency_str
-                        );
+                    print_proxies(&server_clone.get_proxy_pool().list_proxies().await);
+                    let quarantined = server_clone.get_proxy_pool().list_quarantine().await.len();
+                    if quarantined > 0 {
+                        println!("{} {}", "隔离区代理:".yellow().bold(), quarantined.to_string().yellow());
                     }
                     println!();
                 }
@@ -186,7 +508,7 @@ async fn main() -> Result<()> {
                 }
                 "goto" => {
                     // 获取参数
-                    let arg = line.trim().split_whitespace().nth(1).unwrap_or("null");
+                    let arg = line.split_whitespace().nth(1).unwrap_or("null");
 
                     // 尝试将参数解析为 usize 类型的索引
                     match arg.parse::<usize>() {
@@ -213,6 +535,64 @@ async fn main() -> Result<()> {
                         }
                     }
                 }
+                "import" => {
+                    let mut args = line.split_whitespace().skip(1);
+                    let path = args.next();
+                    let format = lokipool::importer::ImportFormat::from_name(args.next().unwrap_or(""));
+                    match (path, format) {
+                        (None, _) => println!("{}", "用法: import <文件> [格式]，格式可选 text/json/clash/subscription/csv".yellow().bold()),
+                        (_, Err(e)) => println!("{} {}", "参数错误:".yellow().bold(), e),
+                        (Some(path), Ok(format)) => match server_clone.get_proxy_pool().import_file(path, format).await {
+                            Ok(added) => println!("{} {} {}",
+                                "导入完成，新增可用代理:".green().bold(),
+                                added.to_string().yellow().bold(),
+                                "个".green().bold()
+                            ),
+                            Err(e) => eprintln!("{} {}", "导入代理失败:".red().bold(), e),
+                        },
+                    }
+                }
+                "export" => {
+                    let mut args = line.split_whitespace().skip(1);
+                    match (args.next(), args.next()) {
+                        (Some(format), Some(path)) => {
+                            let parsed = lokipool::export::ExportFormat::from_name(format)
+                                .and_then(|format| Ok((format, lokipool::export::ExportFilter::parse(args)?)));
+                            match parsed {
+                                Ok((format, filter)) => match server_clone.get_proxy_pool().export(path, format, &filter).await {
+                                    Ok(count) => println!("{} {} {} {}",
+                                        "已导出代理:".green().bold(),
+                                        count.to_string().yellow().bold(),
+                                        "个 ->".green().bold(),
+                                        path
+                                    ),
+                                    Err(e) => eprintln!("{} {}", "导出代理失败:".red().bold(), e),
+                                },
+                                Err(e) => println!("{} {}", "参数错误:".yellow().bold(), e),
+                            }
+                        }
+                        _ => println!("{}", "用法: export <格式> <文件> [tag=a,b] [latency=毫秒] [anonymity=elite]，格式可选 plain/url/json/csv/clash/proxychains".yellow().bold()),
+                    }
+                }
+                "reload" => {
+                    lokipool::reload::reload_config(server_clone.config_handle(), true);
+                }
+                "yield" => {
+                    let yields = server_clone.get_proxy_pool().get_store().source_yield().await;
+                    lokipool::crawler::print_source_yield(&yields);
+                }
+                "usage" => {
+                    lokipool::usage::print_usage(server_clone.usage());
+                }
+                "access" => {
+                    let config = server_clone.config_handle().get();
+                    let access = &config.access;
+                    let show = |rules: &[String]| if rules.is_empty() { "无".to_string() } else { rules.join(", ") };
+                    println!("{} {}", "允许的网段:".green().bold(), show(&access.allow));
+                    println!("{} {}", "拒绝的网段:".green().bold(), show(&access.deny));
+                    println!("{} {}", "免认证网段:".green().bold(), show(&access.no_auth));
+                    println!("{} {}", "已拒绝的连接数:".green().bold(), server_clone.rejected_connections().to_string().yellow());
+                }
                 "quit" => break,
                 "" => {}, // 忽略空行
                 _ => println!("{}", "未知命令，输入 `help` 查看帮助信息".red()),
@@ -223,30 +603,58 @@ async fn main() -> Result<()> {
     });
 
     // 启动服务器
-    let server_handle = tokio::spawn(async move {
-        if let Err(e) = server.run().await {
-            eprintln!("{} {}", "服务器错误:".red().bold(), e);
-        }
+    let mut server_handle = tokio::spawn(async move {
+        server.run().await
     });
 
-    // 等待Ctrl+C信号或用户输入quit
+    // 等待Ctrl+C、SIGTERM信号或用户输入quit
     tokio::select! {
-        _ = signal::ctrl_c() => {
+        _ = shutdown_signal() => {
             println!("\n{}", "接收到终止信号，正在关闭服务器...".yellow().bold());
         }
         _ = input_handle => {
             println!("{}", "用户请求退出，正在关闭服务器...".yellow().bold());
         }
+        result = &mut server_handle => {
+            if let Ok(Err(e)) = result {
+                return Err(anyhow::anyhow!("服务器错误: {}", e));
+            }
+        }
     }
 
     // 中止服务器任务
     server_handle.abort();
+    if let Err(e) = usage.save() {
+        eprintln!("{} {}", "保存流量统计失败:".red().bold(), e);
+    }
     println!("{}", "服务器已关闭".green().bold());
 
     Ok(())
 }
 
 
+// 等待Ctrl+C或SIGTERM(systemd停止服务时发送)
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(_) => {
+                let _ = signal::ctrl_c().await;
+                return;
+            }
+        };
+        tokio::select! {
+            _ = signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = signal::ctrl_c().await;
+    }
+}
+
 pub async fn help() {
     println!("\n可用命令:");
     println!("  help         - 显示帮助信息");
@@ -255,5 +663,11 @@ pub async fn help() {
     println!("  goto <序号>  - 切换到对应代理节点");
     println!("  show         - 显示当前代理");
     println!("  ping         - 测试所有代理并更新延迟");
+    println!("  import <文件> [格式] - 导入Clash、订阅、CSV或JSON格式的代理");
+    println!("  export <格式> <文件> [过滤条件] - 导出代理池，格式可选 plain/url/json/csv/clash/proxychains");
+    println!("  reload       - 重新加载配置文件");
+    println!("  yield        - 显示各代理源的产出统计");
+    println!("  usage        - 显示各用户与客户端IP的流量");
+    println!("  access       - 显示客户端访问控制规则与被拒绝的连接数");
     println!("  quit         - 退出程序\n");
 }
\ No newline at end of file
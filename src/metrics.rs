@@ -0,0 +1,273 @@
+use crate::socks_server::SocksServer;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// 转发方向
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Upload,   // 客户端到上游代理
+    Download, // 上游代理到客户端
+}
+
+// 客户端连接在建立隧道前失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureReason {
+    BadRequest,         // 无法解析的SOCKS5握手或HTTP请求
+    NoAuthMethod,       // 客户端不支持用户名/密码认证
+    AuthFailed,
+    ConnectionLimit,    // 用户或服务器的连接数已达上限
+    UnsupportedCommand,
+    UnsupportedAddress,
+    DestinationDenied,  // 目标地址策略或用户规则不允许
+    QuotaExceeded,
+    NoProxy,            // 没有满足条件的上游代理
+    UpstreamFailed,     // 通过上游代理连接目标失败
+}
+
+impl FailureReason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FailureReason::BadRequest => "bad_request",
+            FailureReason::NoAuthMethod => "no_auth_method",
+            FailureReason::AuthFailed => "auth_failed",
+            FailureReason::ConnectionLimit => "connection_limit",
+            FailureReason::UnsupportedCommand => "unsupported_command",
+            FailureReason::UnsupportedAddress => "unsupported_address",
+            FailureReason::DestinationDenied => "destination_denied",
+            FailureReason::QuotaExceeded => "quota_exceeded",
+            FailureReason::NoProxy => "no_proxy",
+            FailureReason::UpstreamFailed => "upstream_failed",
+        }
+    }
+}
+
+// 单个上游代理的检测与连接计数
+#[derive(Debug, Default, Clone)]
+struct ProxyCounters {
+    check_success: u64,
+    check_failure: u64,
+    connect_success: u64,
+    connect_failure: u64,
+}
+
+#[derive(Debug, Default)]
+struct HealthCheckStats {
+    count: u64,
+    sum: f64,
+    last: f64,
+}
+
+// 运行期间的计数器，由/metrics接口按Prometheus文本格式输出
+#[derive(Default)]
+pub struct Metrics {
+    proxies: Mutex<HashMap<String, ProxyCounters>>,
+    failures: Mutex<BTreeMap<FailureReason, u64>>,
+    uploaded: AtomicU64,
+    downloaded: AtomicU64,
+    health_checks: Mutex<HealthCheckStats>,
+}
+
+impl Metrics {
+    // 记录一批代理检测结果，alive中为检测通过的代理
+    pub fn record_checks<V>(&self, tested: &[String], alive: &HashMap<String, V>) {
+        let mut proxies = self.proxies.lock().unwrap();
+        for address in tested {
+            let counters = proxies.entry(address.clone()).or_default();
+            if alive.contains_key(address) {
+                counters.check_success += 1;
+            } else {
+                counters.check_failure += 1;
+            }
+        }
+    }
+
+    // 记录通过上游代理连接目标的结果
+    pub fn record_connect(&self, address: &str, success: bool) {
+        let mut proxies = self.proxies.lock().unwrap();
+        let counters = proxies.entry(address.to_string()).or_default();
+        if success {
+            counters.connect_success += 1;
+        } else {
+            counters.connect_failure += 1;
+        }
+    }
+
+    // 只保留仍在代理池或隔离区中的代理的计数，避免被移除的代理无限累积
+    pub fn retain_proxies(&self, addresses: &HashSet<String>) {
+        self.proxies.lock().unwrap().retain(|address, _| addresses.contains(address));
+    }
+
+    pub fn record_failure(&self, reason: FailureReason) {
+        *self.failures.lock().unwrap().entry(reason).or_default() += 1;
+    }
+
+    pub fn record_bytes(&self, direction: Direction, bytes: u64) {
+        match direction {
+            Direction::Upload => self.uploaded.fetch_add(bytes, Ordering::Relaxed),
+            Direction::Download => self.downloaded.fetch_add(bytes, Ordering::Relaxed),
+        };
+    }
+
+    pub fn record_health_check(&self, duration: Duration) {
+        let mut stats = self.health_checks.lock().unwrap();
+        let seconds = duration.as_secs_f64();
+        stats.count += 1;
+        stats.sum += seconds;
+        stats.last = seconds;
+    }
+}
+
+// Prometheus标签值中的反斜杠、双引号与换行需要转义
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// 按Prometheus文本格式输出的指标
+struct Exposition {
+    output: String,
+}
+
+impl Exposition {
+    fn metric(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.output, "# HELP {} {}", name, help);
+        let _ = writeln!(self.output, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+        if labels.is_empty() {
+            let _ = writeln!(self.output, "{} {}", name, value);
+            return;
+        }
+        let labels: Vec<String> = labels.iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+            .collect();
+        let _ = writeln!(self.output, "{}{{{}}} {}", name, labels.join(","), value);
+    }
+}
+
+// 渲染/metrics接口的内容
+pub async fn render(server: &SocksServer) -> String {
+    let pool = server.get_proxy_pool();
+    let metrics = pool.metrics();
+    let proxies = pool.list_proxies().await;
+    let quarantine = pool.list_quarantine().await;
+    let mut out = Exposition { output: String::new() };
+
+    out.metric("lokipool_pool_size", "gauge", "代理池与隔离区中的代理总数");
+    out.sample("lokipool_pool_size", &[], proxies.len() + quarantine.len());
+    out.metric("lokipool_proxies", "gauge", "按状态划分的代理数");
+    out.sample("lokipool_proxies", &[("state", "healthy")], proxies.len());
+    out.sample("lokipool_proxies", &[("state", "quarantined")], quarantine.len());
+
+    out.metric("lokipool_proxy_latency_seconds", "gauge", "可用代理最近一次检测的延迟");
+    for proxy in &proxies {
+        out.sample("lokipool_proxy_latency_seconds",
+            &[("proxy", &proxy.address), ("protocol", proxy.protocol.scheme())],
+            proxy.latency.as_secs_f64());
+    }
+
+    let counters = metrics.proxies.lock().unwrap().clone();
+    let mut addresses: Vec<&String> = counters.keys().collect();
+    addresses.sort();
+    out.metric("lokipool_proxy_checks_total", "counter", "代理检测次数");
+    for address in &addresses {
+        let counter = &counters[*address];
+        out.sample("lokipool_proxy_checks_total", &[("proxy", address), ("result", "success")], counter.check_success);
+        out.sample("lokipool_proxy_checks_total", &[("proxy", address), ("result", "failure")], counter.check_failure);
+    }
+    out.metric("lokipool_proxy_connects_total", "counter", "通过代理连接客户端目标的次数");
+    for address in &addresses {
+        let counter = &counters[*address];
+        out.sample("lokipool_proxy_connects_total", &[("proxy", address), ("result", "success")], counter.connect_success);
+        out.sample("lokipool_proxy_connects_total", &[("proxy", address), ("result", "failure")], counter.connect_failure);
+    }
+
+    out.metric("lokipool_client_connections_active", "gauge", "当前的客户端连接数");
+    out.sample("lokipool_client_connections_active", &[], server.active_connections());
+    out.metric("lokipool_client_connections_total", "counter", "接受的客户端连接数");
+    out.sample("lokipool_client_connections_total", &[], server.total_connections());
+    out.metric("lokipool_client_connections_rejected_total", "counter", "因客户端IP被拒绝的连接数");
+    out.sample("lokipool_client_connections_rejected_total", &[], server.rejected_connections());
+
+    out.metric("lokipool_relayed_bytes_total", "counter", "转发的字节数");
+    out.sample("lokipool_relayed_bytes_total", &[("direction", "upload")], metrics.uploaded.load(Ordering::Relaxed));
+    out.sample("lokipool_relayed_bytes_total", &[("direction", "download")], metrics.downloaded.load(Ordering::Relaxed));
+
+    out.metric("lokipool_handshake_failures_total", "counter", "客户端连接在建立隧道前失败的次数");
+    let failures = metrics.failures.lock().unwrap().clone();
+    for (reason, count) in failures {
+        out.sample("lokipool_handshake_failures_total", &[("reason", reason.label())], count);
+    }
+
+    let (count, sum, last) = {
+        let stats = metrics.health_checks.lock().unwrap();
+        (stats.count, stats.sum, stats.last)
+    };
+    out.metric("lokipool_health_check_duration_seconds", "summary", "健康检查耗时");
+    out.sample("lokipool_health_check_duration_seconds_sum", &[], sum);
+    out.sample("lokipool_health_check_duration_seconds_count", &[], count);
+    out.metric("lokipool_health_check_last_duration_seconds", "gauge", "最近一次健康检查的耗时");
+    out.sample("lokipool_health_check_last_duration_seconds", &[], last);
+
+    let yields = pool.get_store().source_yield().await;
+    let sources: Vec<&str> = yields.iter()
+        .map(|stat| if stat.source.is_empty() { "unknown" } else { stat.source.as_str() })
+        .collect();
+    out.metric("lokipool_source_fetched_proxies", "gauge", "代理源获取到的代理数");
+    for (source, stat) in sources.iter().zip(&yields) {
+        out.sample("lokipool_source_fetched_proxies", &[("source", source)], stat.fetched);
+    }
+    out.metric("lokipool_source_alive_proxies", "gauge", "代理源获取的代理中至少检测通过过一次的数量");
+    for (source, stat) in sources.iter().zip(&yields) {
+        out.sample("lokipool_source_alive_proxies", &[("source", source)], stat.alive);
+    }
+    out.metric("lokipool_source_alive_after_24h_proxies", "gauge", "代理源获取的代理中24小时后仍检测通过的数量");
+    for (source, stat) in sources.iter().zip(&yields) {
+        out.sample("lokipool_source_alive_after_24h_proxies", &[("source", source)], stat.alive_after_24h);
+    }
+
+    out.output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn escapes_label_values() {
+        let cases = [
+            ("1.2.3.4:1080", "1.2.3.4:1080"),
+            (r#"a"b"#, r#"a\"b"#),
+            (r"a\b", r"a\\b"),
+            ("a\nb", r"a\nb"),
+            ("\\\"\n", r#"\\\"\n"#),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(escape(value), expected, "{:?}", value);
+        }
+    }
+
+    #[tokio::test]
+    async fn renders_escaped_samples() {
+        let mut config = Config::default();
+        config.proxy.db_file = String::new();
+        config.traffic.usage_file = String::new();
+        let server = SocksServer::new(config);
+        let metrics = server.get_proxy_pool().metrics();
+        metrics.record_connect("bad\"host\\\n:1080", false);
+        metrics.record_failure(FailureReason::NoProxy);
+        metrics.record_bytes(Direction::Download, 42);
+
+        let output = render(&server).await;
+        assert!(output.contains("lokipool_proxy_connects_total{proxy=\"bad\\\"host\\\\\\n:1080\",result=\"failure\"} 1\n"), "{}", output);
+        assert!(output.contains("lokipool_handshake_failures_total{reason=\"no_proxy\"} 1\n"));
+        assert!(output.contains("lokipool_relayed_bytes_total{direction=\"download\"} 42\n"));
+        assert!(output.contains("# TYPE lokipool_pool_size gauge\nlokipool_pool_size 0\n"));
+        // 每个样本占一行，标签值中的换行不会拆开样本
+        assert!(output.lines().all(|line| line.starts_with('#') || line.starts_with("lokipool_")), "{}", output);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::metrics::Direction;
+use crate::usage::Meter;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+// 限速转发时每次读取的字节数
+const CHUNK_SIZE: usize = 16 * 1024;
+
+// 令牌桶限速器，令牌以字节计，最多积累一秒的流量
+// 令牌允许透支，透支的调用方等待到令牌补足为止，多个连接共享同一个令牌桶时总速率不超过限制
+pub struct TokenBucket {
+    rate: f64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let rate = bytes_per_sec.max(1) as f64;
+        TokenBucket {
+            rate,
+            state: Mutex::new(BucketState {
+                tokens: rate,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn rate(&self) -> u64 {
+        self.rate as u64
+    }
+
+    // 取出bytes个令牌，令牌不足时等待
+    pub async fn acquire(&self, bytes: usize) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let elapsed = now.duration_since(state.last).as_secs_f64();
+            state.tokens = (state.tokens + elapsed * self.rate).min(self.rate);
+            state.last = now;
+            state.tokens -= bytes as f64;
+            (state.tokens < 0.0).then(|| Duration::from_secs_f64(-state.tokens / self.rate))
+        };
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+// 复制数据直到读取端结束，每次写入前由计量器限速并记录流量，超出配额时返回错误
+pub async fn copy<R, W>(reader: &mut R, writer: &mut W, meter: &Meter, direction: Direction) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut total = 0;
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            writer.flush().await?;
+            return Ok(total);
+        }
+        meter.consume(n, direction).await?;
+        writer.write_all(&buf[..n]).await?;
+        total += n as u64;
+    }
+}
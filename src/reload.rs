@@ -0,0 +1,87 @@
+use crate::config::ConfigHandle;
+use colored::*;
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+// 配置文件修改时间的轮询间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+// 重载配置并输出结果，返回是否应用了新配置；report_unchanged为false时配置没有变化不输出
+pub fn reload_config(handle: &ConfigHandle, report_unchanged: bool) -> bool {
+    let before = handle.get();
+    let changed = match handle.reload() {
+        Ok(changed) => changed,
+        Err(e) => {
+            eprintln!("{} {}", "重载配置失败:".red().bold(), e);
+            return false;
+        }
+    };
+    if changed.is_empty() {
+        if report_unchanged {
+            println!("{}", "配置没有变化".bright_black());
+        }
+        return false;
+    }
+
+    println!("{} {}", "已重载配置，变化的配置项:".green().bold(), changed.join(", ").yellow());
+    let after = handle.get();
+    if before.proxy.auto_switch != after.proxy.auto_switch {
+        if after.proxy.auto_switch {
+            println!("{} {} {}",
+                "自动切换已开启,间隔:".green().bold(),
+                after.proxy.switch_interval.to_string().yellow().bold(),
+                "秒".green().bold()
+            );
+        } else {
+            println!("{}", "自动切换已关闭".yellow().bold());
+        }
+    }
+    if before.proxy.health_check_switch != after.proxy.health_check_switch {
+        if after.proxy.health_check_switch {
+            println!("{}", "健康检查已启用".green().bold());
+        } else {
+            println!("{}", "健康检查已禁用".yellow().bold());
+        }
+    }
+    true
+}
+
+fn modified_time(handle: &ConfigHandle) -> Option<SystemTime> {
+    fs::metadata(handle.path()?).and_then(|metadata| metadata.modified()).ok()
+}
+
+// 后台监视配置文件：修改时间变化或收到SIGHUP时重载
+pub fn start_config_watch(handle: ConfigHandle) {
+    if handle.path().is_none() {
+        return;
+    }
+
+    let watch_handle = handle.clone();
+    tokio::spawn(async move {
+        let mut last_modified = modified_time(&watch_handle);
+        loop {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+            let modified = modified_time(&watch_handle);
+            if modified.is_some() && modified != last_modified {
+                last_modified = modified;
+                reload_config(&watch_handle, false);
+            }
+        }
+    });
+
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                eprintln!("{} {}", "注册SIGHUP处理失败:".red().bold(), e);
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            println!("\n{}", "接收到SIGHUP，正在重载配置...".cyan().bold());
+            reload_config(&handle, true);
+        }
+    });
+}
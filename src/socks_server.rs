@@ -1,116 +1,406 @@
-use std::net::SocketAddr;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::io::{AsyncRead, AsyncWrite, AsyncReadExt, AsyncWriteExt};
 use anyhow::Result;
+use std::net::IpAddr;
 use std::sync::Arc;
-use crate::proxy_pool::ProxyPool;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use crate::proxy_pool::{Anonymity, ProxyEntry, ProxyPool};
+use crate::export::ExportFilter;
+use crate::{http_proxy, ratelimit, upstream};
+use crate::metrics::{Direction, FailureReason};
+use crate::usage::{Account, ClientLimiters, Meter, Quota, UsageStore};
+use crate::users::{User, UserGuard, UserStore};
 use tracing::{info, error, warn};
-use crate::config::Config;
+use crate::config::{ClientAccess, Config, ConfigHandle, ListenerConfig, ListenerProtocol};
 use colored::*;
 
 #[derive(Clone)]
 pub struct SocksServer {
     proxy_pool: Arc<ProxyPool>,
-    config: Arc<Config>,
+    config: ConfigHandle,
+    users: Arc<UserStore>,
+    rejected: Arc<AtomicU64>, // 因客户端IP被拒绝的连接数
+    active: Arc<AtomicUsize>, // 当前的客户端连接数
+    total: Arc<AtomicU64>,    // 启动以来接受的客户端连接数
+    usage: Arc<UsageStore>,
+    client_limiters: Arc<ClientLimiters>,
 }
 
 impl SocksServer {
     pub fn new(config: Config) -> Self {
-        let proxy_pool = ProxyPool::new(config.clone());
+        Self::with_config(ConfigHandle::new(config))
+    }
+
+    // 使用可热重载的配置创建服务器，认证与日志配置在每个新连接上按当前配置生效
+    pub fn with_config(config: ConfigHandle) -> Self {
+        let proxy_pool = ProxyPool::with_config(config.clone());
+
+        // 打开流量统计文件，失败时退化为内存统计
+        let usage_file = config.get().traffic.usage_file.clone();
+        let usage = if usage_file.is_empty() {
+            UsageStore::in_memory()
+        } else {
+            match UsageStore::open(&usage_file) {
+                Ok(usage) => usage,
+                Err(e) => {
+                    eprintln!("{} {}", "打开流量统计文件失败:".red().bold(), e);
+                    UsageStore::in_memory()
+                }
+            }
+        };
+        let usage = Arc::new(usage);
+        usage.start_flush();
+
         let server = SocksServer {
             proxy_pool: Arc::new(proxy_pool),
-            config: Arc::new(config),
+            config,
+            users: Arc::new(UserStore::new()),
+            rejected: Arc::new(AtomicU64::new(0)),
+            active: Arc::new(AtomicUsize::new(0)),
+            total: Arc::new(AtomicU64::new(0)),
+            usage,
+            client_limiters: Arc::new(ClientLimiters::default()),
         };
-        
-        // 如果开启了自动切换，启动自动切换任务
-        if server.config.proxy.auto_switch {
-            let proxy_pool = Arc::clone(&server.proxy_pool);
-            let switch_interval = server.config.proxy.switch_interval;
-            tokio::spawn(async move {
-                loop {
-                    tokio::time::sleep(tokio::time::Duration::from_secs(switch_interval)).await;
-                    if let Some(proxy) = proxy_pool.next_proxy().await {
-                        // 总是显示自动切换的日志，不受show_connection_log控制
-                        println!("{} {} {} {} {}", 
-                            "[自动切换]".blue().bold(),
-                            "切换到新代理:".green().bold(),
-                            proxy.address.cyan().bold(),
-                            "(延迟:".yellow(),
-                            format!("{}ms)", proxy.latency.as_millis()).yellow()
-                        );
-                    } else {
-                        println!("{} {}", 
-                            "[自动切换]".blue().bold(),
-                            "没有可用的代理".red().bold()
-                        );
+        server.start_auto_switch();
+        server
+    }
+
+    // 自动切换任务，auto_switch关闭时等待配置变化，重载后按新的开关与间隔生效
+    fn start_auto_switch(&self) {
+        let proxy_pool = Arc::clone(&self.proxy_pool);
+        let mut changes = self.config.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let config = proxy_pool.get_config();
+                if !config.proxy.auto_switch {
+                    if changes.changed().await.is_err() {
+                        return;
                     }
+                    continue;
                 }
-            });
-        }
-        
-        server
+
+                let switch_interval = config.proxy.switch_interval.max(1);
+                tokio::select! {
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(switch_interval)) => {}
+                    changed = changes.changed() => {
+                        if changed.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                }
+
+                if let Some(proxy) = proxy_pool.next_proxy().await {
+                    // 总是显示自动切换的日志，不受show_connection_log控制
+                    println!("{} {} {} {} {}", 
+                        "[自动切换]".blue().bold(),
+                        "切换到新代理:".green().bold(),
+                        proxy.address.cyan().bold(),
+                        "(延迟:".yellow(),
+                        format!("{}ms)", proxy.latency.as_millis()).yellow()
+                    );
+                } else {
+                    println!("{} {}", 
+                        "[自动切换]".blue().bold(),
+                        "没有可用的代理".red().bold()
+                    );
+                }
+            }
+        });
     }
 
     pub fn get_proxy_pool(&self) -> &Arc<ProxyPool> {
         &self.proxy_pool
     }
 
-    pub fn get_config(&self) -> &Arc<Config> {
+    // 当前配置的快照
+    pub fn get_config(&self) -> Arc<Config> {
+        self.config.get()
+    }
+
+    pub fn config_handle(&self) -> &ConfigHandle {
         &self.config
     }
 
-    pub fn get_bind_info(&self) -> (String, u16) {
-        (
-            self.config.server.bind_host.clone(),
-            self.config.server.bind_port
-        )
+    pub fn users(&self) -> &Arc<UserStore> {
+        &self.users
+    }
+
+    pub fn usage(&self) -> &Arc<UsageStore> {
+        &self.usage
+    }
+
+    // 启动以来因客户端IP被拒绝的连接数
+    pub fn rejected_connections(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn total_connections(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    // 当前配置中的所有监听器
+    pub fn listeners(&self) -> Vec<ListenerConfig> {
+        self.config.get().listener_configs()
     }
 
+    // 绑定所有监听器并开始接受连接，任一监听地址绑定失败时返回错误
     pub async fn run(&self) -> Result<()> {
-        let addr = format!("{}:{}", 
-            self.config.server.bind_host,
-            self.config.server.bind_port
-        );
-        
-        let listener = TcpListener::bind(&addr).await?;
-        info!("SOCKS5服务器启动在: {}", addr);
+        let mut bound = Vec::new();
+        for (index, listener) in self.listeners().into_iter().enumerate() {
+            let addr = listener.bind_addr();
+            let tcp = TcpListener::bind(&addr).await
+                .map_err(|e| anyhow::anyhow!("监听{}失败: {}", addr, e))?;
+            info!("{}服务器启动在: {} ({})", listener.protocol.name(), addr, listener.display_name());
+            bound.push(self.accept_loop(index, tcp));
+        }
+        futures::future::join_all(bound).await;
+        Ok(())
+    }
 
+    async fn accept_loop(&self, index: usize, listener: TcpListener) {
+        let counter = Arc::new(AtomicUsize::new(0));
         loop {
-            match listener.accept().await {
+            let accepted = listener.accept().await;
+            // 每个连接使用接入时的配置快照，监听器数量变化需要重启，因此下标始终有效
+            let config = self.config.get();
+            let listener_config = match config.listener_configs().into_iter().nth(index) {
+                Some(listener_config) => listener_config,
+                None => continue,
+            };
+            match accepted {
                 Ok((stream, addr)) => {
-                    if self.config.log.show_connection_log {
-                        info!("新的连接来自: {}", addr);
+                    // 接受连接后立即检查客户端IP，被拒绝的连接直接关闭
+                    let access = config.access.check(addr.ip());
+                    if access == ClientAccess::Denied {
+                        self.rejected.fetch_add(1, Ordering::Relaxed);
+                        if config.access.log_rejected {
+                            warn!("拒绝来自{}的连接: 客户端IP不在允许的网段中 -> {}", addr, listener_config.display_name());
+                        }
+                        continue;
+                    }
+                    // 按当前配置检查服务器的总连接数，重载max_connections后立即生效
+                    if self.active.load(Ordering::Relaxed) >= config.server.max_connections {
+                        self.proxy_pool.metrics().record_failure(FailureReason::ConnectionLimit);
+                        if config.log.show_error_log {
+                            warn!("拒绝来自{}的连接: 连接数已达上限{}", addr, config.server.max_connections);
+                        }
+                        continue;
+                    }
+                    if config.log.show_connection_log {
+                        info!("新的连接来自: {} -> {}", addr, listener_config.display_name());
                     }
-                    let proxy_pool = Arc::clone(&self.proxy_pool);
-                    let config = Arc::clone(&self.config);
+                    let connection = Connection {
+                        client_ip: addr.ip().to_canonical(),
+                        no_auth: access == ClientAccess::NoAuth,
+                        proxy_pool: Arc::clone(&self.proxy_pool),
+                        config,
+                        listener: listener_config,
+                        users: Arc::clone(&self.users),
+                        counter: Arc::clone(&counter),
+                        usage: Arc::clone(&self.usage),
+                        client_limiters: Arc::clone(&self.client_limiters),
+                    };
+                    self.total.fetch_add(1, Ordering::Relaxed);
+                    let active = Arc::clone(&self.active);
+                    active.fetch_add(1, Ordering::Relaxed);
                     tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(stream, proxy_pool, Arc::clone(&config)).await {
-                            if config.log.show_error_log {
+                        if let Err(e) = connection.handle(stream).await {
+                            if connection.config.log.show_error_log {
                                 error!("处理连接错误: {}", e);
                             }
                         }
+                        active.fetch_sub(1, Ordering::Relaxed);
                     });
                 }
                 Err(e) => {
-                    if self.config.log.show_error_log {
+                    if config.log.show_error_log {
                         warn!("接受连接失败: {}", e);
                     }
                 }
             }
         }
     }
+}
 
-    async fn handle_connection(client: TcpStream, proxy_pool: Arc<ProxyPool>, config: Arc<Config>) -> Result<()> {
+// 单个客户端连接的上下文
+pub(crate) struct Connection {
+    pub(crate) proxy_pool: Arc<ProxyPool>,
+    pub(crate) config: Arc<Config>,
+    pub(crate) listener: ListenerConfig,
+    client_ip: IpAddr,
+    no_auth: bool,             // 客户端在免认证网段中
+    users: Arc<UserStore>,
+    counter: Arc<AtomicUsize>, // 所属监听器的轮换计数
+    usage: Arc<UsageStore>,
+    client_limiters: Arc<ClientLimiters>,
+}
+
+// 认证失败的原因
+#[derive(Debug)]
+pub(crate) enum AuthError {
+    InvalidCredentials,
+    ConnectionLimit,
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::InvalidCredentials => f.write_str("认证失败"),
+            AuthError::ConnectionLimit => f.write_str("用户连接数已达上限"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl AuthError {
+    pub(crate) fn reason(&self) -> FailureReason {
+        match self {
+            AuthError::InvalidCredentials => FailureReason::AuthFailed,
+            AuthError::ConnectionLimit => FailureReason::ConnectionLimit,
+        }
+    }
+}
+
+// 已认证的客户端，使用用户文件中的用户认证时带有该用户的策略，连接结束时释放连接名额
+#[derive(Default)]
+pub(crate) struct Session {
+    user: Option<Arc<User>>,
+    _guard: Option<UserGuard>,
+}
+
+impl Session {
+    pub(crate) fn allows_destination(&self, host: &str, port: u16) -> bool {
+        self.user.as_ref().is_none_or(|user| user.allows_destination(host, port))
+    }
+}
+
+impl Connection {
+    async fn handle(&self, client: TcpStream) -> Result<()> {
+        let protocol = match self.listener.protocol {
+            ListenerProtocol::Mixed => {
+                // SOCKS5握手的第一个字节为版本号5，HTTP请求以方法名开头
+                let mut first = [0u8; 1];
+                client.peek(&mut first).await?;
+                if first[0] == 0x05 { ListenerProtocol::Socks5 } else { ListenerProtocol::Http }
+            }
+            protocol => protocol,
+        };
+        match protocol {
+            ListenerProtocol::Http => http_proxy::handle(client, self).await,
+            _ => self.handle_socks(client).await,
+        }
+    }
+
+    // 是否需要客户端认证
+    pub(crate) fn requires_auth(&self) -> bool {
+        self.listener.use_auth && !self.no_auth
+    }
+
+    // 记录建立隧道前失败的原因
+    pub(crate) fn record_failure(&self, reason: FailureReason) {
+        self.proxy_pool.metrics().record_failure(reason);
+    }
+
+    // 检查目标地址，先按全局的目标地址策略，再按用户的规则，不允许时返回原因
+    pub(crate) fn check_destination(&self, session: &Session, host: &str, port: u16) -> Result<()> {
+        let denied = if !self.config.destination.allows(host, port) {
+            anyhow::anyhow!("目标地址被访问策略拒绝: {}:{}", host, port)
+        } else if !session.allows_destination(host, port) {
+            anyhow::anyhow!("用户不允许访问该目标: {}:{}", host, port)
+        } else {
+            return Ok(());
+        };
+        self.record_failure(FailureReason::DestinationDenied);
+        Err(denied)
+    }
+
+    // 通过上游代理连接目标，记录该代理的连接结果
+    pub(crate) async fn connect_upstream(&self, proxy: &ProxyEntry, host: &str, port: u16) -> Result<TcpStream> {
+        let result = upstream::connect(proxy, host, port).await;
+        self.proxy_pool.metrics().record_connect(&proxy.address, result.is_ok());
+        if result.is_err() {
+            self.record_failure(FailureReason::UpstreamFailed);
+        }
+        result
+    }
+
+    // 创建连接的计量器，客户端IP或用户的流量配额已用完时返回错误
+    pub(crate) fn meter(&self, session: &Session) -> Result<Meter> {
+        let traffic = &self.config.traffic;
+        let mut accounts = vec![(
+            Account::Client(self.client_ip),
+            Quota::from_mb(traffic.client_daily_quota, traffic.client_monthly_quota),
+        )];
+        let mut limiters: Vec<_> = self.client_limiters
+            .get(self.client_ip, traffic.client_bandwidth_limit.saturating_mul(1024))
+            .into_iter()
+            .collect();
+        if let Some(user) = &session.user {
+            accounts.push((Account::User(user.username.clone()), user.quota));
+            limiters.extend(user.limiter().cloned());
+        }
+        if let Err(e) = self.usage.check(&accounts) {
+            self.record_failure(FailureReason::QuotaExceeded);
+            return Err(e);
+        }
+        Ok(Meter::new(Arc::clone(&self.usage), Arc::clone(self.proxy_pool.metrics()), accounts, limiters))
+    }
+
+    // 按监听器的策略与分组条件选择上游代理，用户限定了分组时只使用其中的代理
+    pub(crate) async fn select_proxy(&self, session: &Session) -> Option<ProxyEntry> {
+        let filter = ExportFilter {
+            tags: self.listener.tags.clone(),
+            max_latency: (self.listener.max_latency > 0).then(|| Duration::from_millis(self.listener.max_latency)),
+            anonymity: Anonymity::from_name(&self.listener.anonymity),
+        };
+        let user = session.user.as_deref();
+        self.proxy_pool.select_proxy(
+            self.listener.strategy,
+            |proxy| filter.matches(proxy) && user.is_none_or(|user| user.allows_proxy(proxy)),
+            &self.counter,
+        ).await
+    }
+
+    // 验证用户名与密码，先匹配监听器自身的账号，再匹配用户文件中的用户
+    pub(crate) async fn authenticate(&self, username: &str, password: &str) -> Result<Session, AuthError> {
+        if !self.listener.username.is_empty()
+            && username == self.listener.username
+            && self.listener.password.matches(password)
+        {
+            return Ok(Session::default());
+        }
+        if self.config.proxy.users_file.is_empty() {
+            return Err(AuthError::InvalidCredentials);
+        }
+        let user = self.users.authenticate(&self.config.proxy.users_file, username, password).await
+            .ok_or(AuthError::InvalidCredentials)?;
+        let guard = self.users.acquire(&user).ok_or(AuthError::ConnectionLimit)?;
+        Ok(Session {
+            user: Some(user),
+            _guard: Some(guard),
+        })
+    }
+
+    async fn handle_socks(&self, client: TcpStream) -> Result<()> {
+        let config = &self.config;
         let (mut inbound_reader, mut inbound_writer) = client.into_split();
 
         // 处理SOCKS5握手
-        handle_handshake(&mut inbound_reader, &mut inbound_writer, &config).await?;
+        let session = handle_handshake(&mut inbound_reader, &mut inbound_writer, self).await?;
 
         // 读取SOCKS5请求
         let mut buf = [0u8; 4];
         inbound_reader.read_exact(&mut buf).await?;
 
         if buf[0] != 0x05 || buf[1] != 0x01 {
+            self.record_failure(FailureReason::UnsupportedCommand);
             return Err(anyhow::anyhow!("不支持的SOCKS5命令"));
         }
 
@@ -131,19 +421,36 @@ impl SocksServer {
             0x04 => { // IPv6
                 let mut addr = [0u8; 16];
                 inbound_reader.read_exact(&mut addr).await?;
+                self.record_failure(FailureReason::UnsupportedAddress);
                 return Err(anyhow::anyhow!("暂不支持IPv6"));
             },
-            _ => return Err(anyhow::anyhow!("不支持的地址类型")),
+            _ => {
+                self.record_failure(FailureReason::UnsupportedAddress);
+                return Err(anyhow::anyhow!("不支持的地址类型"));
+            }
         };
 
         // 读取端口
         let port = inbound_reader.read_u16().await?;
-        let _target = format!("{}:{}", target_addr, port);
+
+        // 规则不允许的目标或流量配额已用完
+        let meter = match self.check_destination(&session, &target_addr, port).and_then(|_| self.meter(&session)) {
+            Ok(meter) => meter,
+            Err(e) => {
+                let response = [
+                    0x05, 0x02, 0x00, 0x01,
+                    0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00,
+                ];
+                inbound_writer.write_all(&response).await?;
+                return Err(e);
+            }
+        };
 
         // 获取代理
-        if let Some(proxy) = proxy_pool.get_current_proxy().await {
-            let proxy_addr: SocketAddr = proxy.address.parse()?;
-            let mut upstream = match TcpStream::connect(proxy_addr).await {
+        if let Some(proxy) = self.select_proxy(&session).await {
+            // 通过上游代理(SOCKS5或HTTP CONNECT)连接目标
+            let upstream = match self.connect_upstream(&proxy, &target_addr, port).await {
                 Ok(stream) => stream,
                 Err(e) => {
                     if config.log.show_error_log {
@@ -160,78 +467,6 @@ impl SocksServer {
                 }
             };
 
-            // 与上游SOCKS5服务器进行握手
-            upstream.write_all(&[0x05, 0x01, 0x00]).await?;
-            let mut response = [0u8; 2];
-            upstream.read_exact(&mut response).await?;
-            
-            if response[0] != 0x05 || response[1] != 0x00 {
-                eprintln!("上游代理握手失败");
-                return Ok(());
-            }
-
-            // 发送连接请求到上游代理
-            let mut request = Vec::new();
-            request.extend_from_slice(&[0x05, 0x01, 0x00]); // VER, CMD, RSV
-            
-            match atyp {
-                0x01 => { // IPv4
-                    request.push(0x01);
-                    for octet in target_addr.split('.') {
-                        request.push(octet.parse::<u8>()?);
-                    }
-                },
-                0x03 => { // Domain
-                    request.push(0x03);
-                    request.push(target_addr.len() as u8);
-                    request.extend_from_slice(target_addr.as_bytes());
-                },
-                _ => unreachable!(),
-            }
-            
-            // 添加端口
-            request.extend_from_slice(&port.to_be_bytes());
-            
-            // 发送请求到上游代理
-            upstream.write_all(&request).await?;
-            
-            // 读取上游代理响应
-            let mut response = [0u8; 4];
-            upstream.read_exact(&mut response).await?;
-            
-            if response[1] != 0x00 {
-                if config.log.show_error_log {
-                    eprintln!("上游代理连接目标失败");
-                }
-                let response = [
-                    0x05, 0x04, 0x00, 0x01,
-                    0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00,
-                ];
-                inbound_writer.write_all(&response).await?;
-                return Ok(());
-            }
-            
-            // 跳过绑定地址和端口
-            match response[3] {
-                0x01 => { // IPv4
-                    let mut addr = [0u8; 4];
-                    upstream.read_exact(&mut addr).await?;
-                },
-                0x03 => { // Domain
-                    let len = upstream.read_u8().await?;
-                    let mut domain = vec![0u8; len as usize];
-                    upstream.read_exact(&mut domain).await?;
-                },
-                0x04 => { // IPv6
-                    let mut addr = [0u8; 16];
-                    upstream.read_exact(&mut addr).await?;
-                },
-                _ => return Err(anyhow::anyhow!("上游代理返回了不支持的地址类型")),
-            }
-            let mut port = [0u8; 2];
-            upstream.read_exact(&mut port).await?;
-
             // 发送成功响应给客户端
             let response = [
                 0x05, 0x00, 0x00, 0x01,
@@ -241,28 +476,10 @@ impl SocksServer {
             inbound_writer.write_all(&response).await?;
 
             // 双向转发数据
-            let (mut upstream_reader, mut upstream_writer) = upstream.into_split();
-            let client_to_proxy = tokio::io::copy(&mut inbound_reader, &mut upstream_writer);
-            let proxy_to_client = tokio::io::copy(&mut upstream_reader, &mut inbound_writer);
-            
-            tokio::select! {
-                res = client_to_proxy => {
-                    if let Err(e) = res {
-                        if config.log.show_error_log {
-                            eprintln!("客户端到代理传输错误: {}", e);
-                        }
-                    }
-                },
-                res = proxy_to_client => {
-                    if let Err(e) = res {
-                        if config.log.show_error_log {
-                            eprintln!("代理到客户端传输错误: {}", e);
-                        }
-                    }
-                }
-            }
+            relay(inbound_reader, inbound_writer, upstream, config, &meter).await;
         } else {
             // 发送失败响应
+            self.record_failure(FailureReason::NoProxy);
             let response = [
                 0x05, 0x01, 0x00, 0x01,
                 0x00, 0x00, 0x00, 0x00,
@@ -278,7 +495,35 @@ impl SocksServer {
     }
 }
 
-async fn handle_handshake<R, W>(reader: &mut R, writer: &mut W, config: &Arc<Config>) -> Result<()>
+// 在客户端与上游代理之间双向转发数据，任一方向结束或超出流量配额时关闭连接，两个方向的流量都由meter限速与计量
+pub(crate) async fn relay<R, W>(mut inbound_reader: R, mut inbound_writer: W, upstream: TcpStream, config: &Config, meter: &Meter)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let (mut upstream_reader, mut upstream_writer) = upstream.into_split();
+    let client_to_proxy = ratelimit::copy(&mut inbound_reader, &mut upstream_writer, meter, Direction::Upload);
+    let proxy_to_client = ratelimit::copy(&mut upstream_reader, &mut inbound_writer, meter, Direction::Download);
+
+    tokio::select! {
+        res = client_to_proxy => {
+            if let Err(e) = res {
+                if config.log.show_error_log {
+                    eprintln!("客户端到代理传输错误: {}", e);
+                }
+            }
+        },
+        res = proxy_to_client => {
+            if let Err(e) = res {
+                if config.log.show_error_log {
+                    eprintln!("代理到客户端传输错误: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_handshake<R, W>(reader: &mut R, writer: &mut W, connection: &Connection) -> Result<Session>
 where
     R: AsyncRead + Unpin,
     W: AsyncWrite + Unpin,
@@ -288,6 +533,7 @@ where
     reader.read_exact(&mut method_selection).await?;
     
     if method_selection[0] != 0x05 {
+        connection.record_failure(FailureReason::BadRequest);
         return Err(anyhow::anyhow!("不支持的SOCKS版本"));
     }
     
@@ -296,7 +542,7 @@ where
     reader.read_exact(&mut methods).await?;
 
     // 检查是否需要认证
-    if config.proxy.use_auth {
+    let session = if connection.requires_auth() {
         // 查找客户端是否支持用户名/密码认证 (0x02)
         if methods.contains(&0x02) {
             // 回复使用用户名/密码认证方法
@@ -308,6 +554,7 @@ where
             reader.read_exact(&mut auth_version).await?;
             
             if auth_version[0] != 0x01 {
+                connection.record_failure(FailureReason::BadRequest);
                 return Err(anyhow::anyhow!("不支持的认证版本"));
             }
             
@@ -324,18 +571,24 @@ where
             let password = String::from_utf8(password)?;
             
             // 验证用户名和密码
-            if username == config.proxy.username && password == config.proxy.password {
-                // 认证成功
-                writer.write_all(&[0x01, 0x00]).await?;
-                writer.flush().await?;
-            } else {
-                // 认证失败
-                writer.write_all(&[0x01, 0x01]).await?;
-                writer.flush().await?;
-                return Err(anyhow::anyhow!("认证失败"));
+            match connection.authenticate(&username, &password).await {
+                Ok(session) => {
+                    // 认证成功
+                    writer.write_all(&[0x01, 0x00]).await?;
+                    writer.flush().await?;
+                    session
+                }
+                Err(e) => {
+                    // 认证失败
+                    connection.record_failure(e.reason());
+                    writer.write_all(&[0x01, 0x01]).await?;
+                    writer.flush().await?;
+                    return Err(anyhow::anyhow!("{}: {}", e, username));
+                }
             }
         } else {
             // 客户端不支持我们需要的认证方法
+            connection.record_failure(FailureReason::NoAuthMethod);
             writer.write_all(&[0x05, 0xFF]).await?;
             writer.flush().await?;
             return Err(anyhow::anyhow!("客户端不支持所需的认证方法"));
@@ -344,7 +597,8 @@ where
         // 不需要认证，回复使用无认证方法
         writer.write_all(&[0x05, 0x00]).await?;
         writer.flush().await?;
-    }
+        Session::default()
+    };
 
-    Ok(())
+    Ok(session)
 } 
\ No newline at end of file
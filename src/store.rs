@@ -0,0 +1,337 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use crate::fsutil::write_atomic;
+use crate::proxy_pool::{Anonymity, Credentials, ProxyEntry, ProxyProtocol};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+// 每个代理保留的检测历史条数
+const HISTORY_LIMIT: usize = 50;
+// 统计“24小时后仍可用”时使用的时间窗口
+const YIELD_WINDOW_SECS: u64 = 24 * 60 * 60;
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+// 单次检测记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckRecord {
+    pub time: u64,               // 检测时间(unix秒)
+    pub latency_ms: Option<u64>, // 延迟，None表示检测失败
+}
+
+// 代理的来源信息，记录由哪个代理源、哪条查询语句在何时获取
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub source: String,
+    #[serde(default)]
+    pub query: String,
+    pub fetched_at: u64,         // 获取时间(unix秒)
+}
+
+// 代理的持久化记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyRecord {
+    pub address: String,
+    pub first_seen: u64,          // 首次发现时间
+    pub last_seen: u64,           // 最近一次出现在待测列表中的时间
+    #[serde(default)]
+    pub last_alive: Option<u64>,  // 最近一次检测通过的时间
+    #[serde(default)]
+    pub provenance: Option<Provenance>, // 代理来源，手动添加的代理为None
+    #[serde(default)]
+    pub protocol: ProxyProtocol,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credentials: Option<Credentials>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anonymity: Option<Anonymity>, // 最近一次判断的匿名度
+    #[serde(default)]
+    pub history: Vec<CheckRecord>,
+}
+
+impl ProxyRecord {
+    fn new(address: &str, now: u64) -> Self {
+        ProxyRecord {
+            address: address.to_string(),
+            first_seen: now,
+            last_seen: now,
+            last_alive: None,
+            provenance: None,
+            protocol: ProxyProtocol::Socks5,
+            credentials: None,
+            tags: Vec::new(),
+            anonymity: None,
+            history: Vec::new(),
+        }
+    }
+
+    // 最近一次检测的延迟，最近一次检测失败时返回None
+    pub fn last_latency(&self) -> Option<Duration> {
+        self.history.last()
+            .and_then(|check| check.latency_ms)
+            .map(Duration::from_millis)
+    }
+}
+
+// 单个代理源的产出统计
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceYield {
+    pub source: String,          // 代理源名称，为空表示来源未知
+    pub fetched: usize,          // 获取到的代理数
+    pub alive: usize,            // 至少检测通过过一次的代理数
+    pub alive_after_24h: usize,  // 获取24小时后仍检测通过的代理数
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoreFile {
+    proxies: Vec<ProxyRecord>,
+}
+
+// 代理数据库，以JSON文件形式保存在磁盘上，重启后可恢复上次的可用代理
+pub struct ProxyStore {
+    path: Option<PathBuf>,
+    records: Mutex<HashMap<String, ProxyRecord>>,
+    saving: Mutex<()>, // 串行写入数据库文件，避免较早的快照覆盖较新的快照
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl ProxyStore {
+    // 不落盘的内存数据库
+    pub fn in_memory() -> Self {
+        ProxyStore {
+            path: None,
+            records: Mutex::new(HashMap::new()),
+            saving: Mutex::new(()),
+        }
+    }
+
+    // 从文件加载数据库，文件不存在时返回空数据库
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut records = HashMap::new();
+
+        if path.exists() {
+            let content = fs::read_to_string(&path)?;
+            if !content.trim().is_empty() {
+                let file: StoreFile = serde_json::from_str(&content)
+                    .map_err(|e| anyhow::anyhow!("解析代理数据库失败: {}", e))?;
+                for record in file.proxies {
+                    records.insert(record.address.clone(), record);
+                }
+            }
+        }
+
+        Ok(ProxyStore {
+            path: Some(path),
+            records: Mutex::new(records),
+            saving: Mutex::new(()),
+        })
+    }
+
+    // 记录一批检测结果，alive中为检测通过的代理及其延迟
+    pub async fn record_checks(&self, tested: &[String], alive: &HashMap<String, Duration>) {
+        let now = unix_now();
+        let mut records = self.records.lock().await;
+
+        for address in tested {
+            let record = records.entry(address.clone())
+                .or_insert_with(|| ProxyRecord::new(address, now));
+            record.last_seen = now;
+
+            let latency_ms = alive.get(address).map(|latency| latency.as_millis() as u64);
+            if latency_ms.is_some() {
+                record.last_alive = Some(now);
+            }
+            record.history.push(CheckRecord { time: now, latency_ms });
+            if record.history.len() > HISTORY_LIMIT {
+                let overflow = record.history.len() - HISTORY_LIMIT;
+                record.history.drain(..overflow);
+            }
+        }
+    }
+
+    // 记录代理的协议、认证信息、标签与来源，已有来源的代理保留最早的来源
+    pub async fn record_metadata(&self, entries: &[ProxyEntry]) {
+        if entries.is_empty() {
+            return;
+        }
+
+        let now = unix_now();
+        let mut records = self.records.lock().await;
+        for entry in entries {
+            let record = records.entry(entry.address.clone())
+                .or_insert_with(|| ProxyRecord::new(&entry.address, now));
+            record.protocol = entry.protocol;
+            record.credentials = entry.credentials.clone();
+            record.tags = entry.tags.clone();
+            if record.provenance.is_none() {
+                record.provenance = entry.provenance.clone();
+            }
+        }
+    }
+
+    // 记录检测时判断出的匿名度，未判断的代理保留原有结果
+    pub async fn record_anonymity(&self, entries: &[ProxyEntry]) {
+        let mut records = self.records.lock().await;
+        for entry in entries {
+            if let (Some(record), Some(anonymity)) = (records.get_mut(&entry.address), entry.anonymity) {
+                record.anonymity = Some(anonymity);
+            }
+        }
+    }
+
+    // 查询单个代理的持久化记录
+    pub async fn get(&self, address: &str) -> Option<ProxyRecord> {
+        self.records.lock().await.get(address).cloned()
+    }
+
+    pub async fn contains(&self, address: &str) -> bool {
+        self.records.lock().await.contains_key(address)
+    }
+
+    // 该代理最近max_age_secs秒内是否检测过
+    pub async fn checked_within(&self, address: &str, max_age_secs: u64) -> bool {
+        let cutoff = unix_now().saturating_sub(max_age_secs);
+        self.records.lock().await.get(address)
+            .and_then(|record| record.history.last())
+            .is_some_and(|check| check.time > cutoff)
+    }
+
+    // 最近一次检测通过的代理，按延迟排序
+    pub async fn known_good(&self) -> Vec<ProxyRecord> {
+        let records = self.records.lock().await;
+        let mut good: Vec<ProxyRecord> = records.values()
+            .filter(|record| record.last_latency().is_some())
+            .cloned()
+            .collect();
+        good.sort_by_key(|record| record.last_latency());
+        good
+    }
+
+    // 按代理源统计产出，用于评估各代理源(及其API配额)的价值
+    pub async fn source_yield(&self) -> Vec<SourceYield> {
+        let records = self.records.lock().await;
+        let mut yields: HashMap<String, SourceYield> = HashMap::new();
+
+        for record in records.values() {
+            let (source, fetched_at) = match &record.provenance {
+                Some(provenance) => (provenance.source.clone(), provenance.fetched_at),
+                None => (String::new(), record.first_seen),
+            };
+            let stat = yields.entry(source.clone()).or_insert_with(|| SourceYield {
+                source,
+                fetched: 0,
+                alive: 0,
+                alive_after_24h: 0,
+            });
+            stat.fetched += 1;
+            if let Some(last_alive) = record.last_alive {
+                stat.alive += 1;
+                if last_alive >= fetched_at + YIELD_WINDOW_SECS {
+                    stat.alive_after_24h += 1;
+                }
+            }
+        }
+
+        let mut list: Vec<SourceYield> = yields.into_values().collect();
+        list.sort_by(|a, b| a.source.cmp(&b.source));
+        list
+    }
+
+    // 删除超过max_age_days天未出现在待测列表中也未检测通过的代理，返回删除的数量，0表示不清理
+    pub async fn prune(&self, max_age_days: u64) -> usize {
+        if max_age_days == 0 {
+            return 0;
+        }
+        let cutoff = unix_now().saturating_sub(max_age_days.saturating_mul(DAY_SECS));
+        let mut records = self.records.lock().await;
+        let before = records.len();
+        records.retain(|_, record| record.last_seen.max(record.last_alive.unwrap_or(0)) >= cutoff);
+        before - records.len()
+    }
+
+    pub async fn records(&self) -> Vec<ProxyRecord> {
+        let records = self.records.lock().await;
+        let mut list: Vec<ProxyRecord> = records.values().cloned().collect();
+        list.sort_by(|a, b| a.address.cmp(&b.address));
+        list
+    }
+
+    // 原子写回磁盘，写入期间持有记录锁，避免并发保存时旧快照覆盖新快照
+    pub async fn save(&self) -> anyhow::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        // 复制快照后释放记录锁，序列化与同步写盘在阻塞线程中进行
+        let _saving = self.saving.lock().await;
+        let mut proxies: Vec<ProxyRecord> = self.records.lock().await.values().cloned().collect();
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || {
+            proxies.sort_by(|a, b| a.address.cmp(&b.address));
+            let content = serde_json::to_string_pretty(&StoreFile { proxies })?;
+            write_atomic(&path, content.as_bytes())?;
+            Ok(())
+        }).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn prune_removes_only_stale_records() {
+        let store = ProxyStore::in_memory();
+        let alive = HashMap::from([("1.1.1.1:1080".to_string(), Duration::from_millis(100))]);
+        store.record_checks(&["1.1.1.1:1080".to_string(), "2.2.2.2:1080".to_string()], &alive).await;
+        {
+            let mut records = store.records.lock().await;
+            let stale = records.get_mut("2.2.2.2:1080").unwrap();
+            stale.last_seen = unix_now() - 31 * DAY_SECS;
+        }
+
+        assert_eq!(store.prune(0).await, 0);
+        assert_eq!(store.prune(30).await, 1);
+        assert!(store.contains("1.1.1.1:1080").await);
+        assert!(!store.contains("2.2.2.2:1080").await);
+    }
+
+    #[tokio::test]
+    async fn failed_records_become_retestable() {
+        let store = ProxyStore::in_memory();
+        store.record_checks(&["3.3.3.3:1080".to_string()], &HashMap::new()).await;
+        assert!(store.checked_within("3.3.3.3:1080", 3600).await);
+        assert!(!store.checked_within("4.4.4.4:1080", 3600).await);
+
+        store.records.lock().await.get_mut("3.3.3.3:1080").unwrap().history[0].time -= 7200;
+        assert!(!store.checked_within("3.3.3.3:1080", 3600).await);
+    }
+
+    #[tokio::test]
+    async fn save_and_reopen() {
+        let dir = std::env::temp_dir().join(format!("lokipool-store-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("proxies.db.json");
+
+        let store = ProxyStore::open(&path).unwrap();
+        let alive = HashMap::from([("1.1.1.1:1080".to_string(), Duration::from_millis(100))]);
+        store.record_checks(&["1.1.1.1:1080".to_string(), "2.2.2.2:1080".to_string()], &alive).await;
+        store.save().await.unwrap();
+
+        let reopened = ProxyStore::open(&path).unwrap();
+        let good: Vec<String> = reopened.known_good().await.into_iter().map(|record| record.address).collect();
+        assert_eq!(good, ["1.1.1.1:1080"]);
+        assert_eq!(reopened.records.lock().await.len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use crate::proxy_pool::{Credentials, ProxyEntry, ProxyProtocol};
+use anyhow::Result;
+use base64::{Engine as _, engine::general_purpose};
+use std::net::IpAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// HTTP CONNECT响应头的最大长度
+const MAX_HTTP_HEADER: usize = 8192;
+
+// 通过上游代理连接目标地址，返回已建立隧道的连接
+pub async fn connect(proxy: &ProxyEntry, host: &str, port: u16) -> Result<TcpStream> {
+    let mut stream = TcpStream::connect(proxy.address.as_str()).await
+        .map_err(|e| anyhow::anyhow!("连接上游代理失败: {} - {}", proxy.address, e))?;
+
+    match proxy.protocol {
+        ProxyProtocol::Socks5 => socks5_connect(&mut stream, proxy.credentials.as_ref(), host, port).await?,
+        ProxyProtocol::Http => http_connect(&mut stream, proxy.credentials.as_ref(), host, port).await?,
+    }
+    Ok(stream)
+}
+
+async fn socks5_connect(stream: &mut TcpStream, credentials: Option<&Credentials>, host: &str, port: u16) -> Result<()> {
+    // 有认证信息时同时声明无认证与用户名密码认证(RFC1929)
+    match credentials {
+        Some(_) => stream.write_all(&[0x05, 0x02, 0x00, 0x02]).await?,
+        None => stream.write_all(&[0x05, 0x01, 0x00]).await?,
+    }
+    let mut response = [0u8; 2];
+    stream.read_exact(&mut response).await?;
+    if response[0] != 0x05 {
+        return Err(anyhow::anyhow!("上游代理握手失败"));
+    }
+
+    match (response[1], credentials) {
+        (0x00, _) => {}
+        (0x02, Some(credentials)) => {
+            let username = credentials.username.as_bytes();
+            let password = credentials.password.as_bytes();
+            if username.len() > 255 || password.len() > 255 {
+                return Err(anyhow::anyhow!("上游代理用户名或密码过长"));
+            }
+            let mut auth = vec![0x01, username.len() as u8];
+            auth.extend_from_slice(username);
+            auth.push(password.len() as u8);
+            auth.extend_from_slice(password);
+            stream.write_all(&auth).await?;
+
+            let mut response = [0u8; 2];
+            stream.read_exact(&mut response).await?;
+            if response[1] != 0x00 {
+                return Err(anyhow::anyhow!("上游代理认证失败"));
+            }
+        }
+        _ => return Err(anyhow::anyhow!("上游代理握手失败")),
+    }
+
+    // 发送连接请求
+    let mut request = vec![0x05, 0x01, 0x00]; // VER, CMD, RSV
+    request.extend_from_slice(&socks5_address(host)?);
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut response = [0u8; 4];
+    stream.read_exact(&mut response).await?;
+    if response[1] != 0x00 {
+        return Err(anyhow::anyhow!("上游代理连接目标失败"));
+    }
+
+    // 跳过绑定地址和端口
+    match response[3] {
+        0x01 => { // IPv4
+            let mut addr = [0u8; 4];
+            stream.read_exact(&mut addr).await?;
+        },
+        0x03 => { // Domain
+            let len = stream.read_u8().await?;
+            let mut domain = vec![0u8; len as usize];
+            stream.read_exact(&mut domain).await?;
+        },
+        0x04 => { // IPv6
+            let mut addr = [0u8; 16];
+            stream.read_exact(&mut addr).await?;
+        },
+        _ => return Err(anyhow::anyhow!("上游代理返回了不支持的地址类型")),
+    }
+    let mut port = [0u8; 2];
+    stream.read_exact(&mut port).await?;
+    Ok(())
+}
+
+// SOCKS5请求中的目标地址(ATYP与地址)，IPv6地址可带方括号
+fn socks5_address(host: &str) -> Result<Vec<u8>> {
+    let unbracketed = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+    let address = match unbracketed.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => [&[0x01][..], &ip.octets()].concat(),
+        Ok(IpAddr::V6(ip)) => [&[0x04][..], &ip.octets()].concat(),
+        Err(_) => {
+            if host.len() > 255 {
+                return Err(anyhow::anyhow!("目标域名过长"));
+            }
+            [&[0x03, host.len() as u8][..], host.as_bytes()].concat()
+        }
+    };
+    Ok(address)
+}
+
+// CONNECT请求的目标，IPv6地址需要加方括号
+fn http_target(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+async fn http_connect(stream: &mut TcpStream, credentials: Option<&Credentials>, host: &str, port: u16) -> Result<()> {
+    let target = http_target(host, port);
+    let mut request = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", target, target);
+    if let Some(credentials) = credentials {
+        let token = general_purpose::STANDARD.encode(format!("{}:{}", credentials.username, credentials.password));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // 逐字节读取响应头，避免把隧道中的数据读走
+    let mut header = Vec::new();
+    while !header.ends_with(b"\r\n\r\n") {
+        if header.len() >= MAX_HTTP_HEADER {
+            return Err(anyhow::anyhow!("上游代理响应头过长"));
+        }
+        header.push(stream.read_u8().await?);
+    }
+
+    let status_line = String::from_utf8_lossy(&header);
+    let status = status_line.split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow::anyhow!("上游代理响应格式错误"))?;
+    if !(200..300).contains(&status) {
+        return Err(anyhow::anyhow!("上游代理拒绝CONNECT请求: HTTP状态码 {}", status));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_socks5_addresses() {
+        let cases: &[(&str, &[u8])] = &[
+            ("1.2.3.4", &[0x01, 1, 2, 3, 4]),
+            ("2001:db8::1", &[0x04, 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]),
+            ("[::1]", &[0x04, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]),
+            ("a.io", &[0x03, 4, b'a', b'.', b'i', b'o']),
+        ];
+        for (host, expected) in cases {
+            assert_eq!(socks5_address(host).unwrap(), *expected, "{}", host);
+        }
+        assert!(socks5_address(&"a".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn brackets_ipv6_connect_targets() {
+        assert_eq!(http_target("example.com", 443), "example.com:443");
+        assert_eq!(http_target("1.2.3.4", 443), "1.2.3.4:443");
+        assert_eq!(http_target("2001:db8::1", 443), "[2001:db8::1]:443");
+        assert_eq!(http_target("[2001:db8::1]", 443), "[2001:db8::1]:443");
+    }
+}
@@ -0,0 +1,512 @@
+use crate::fsutil::write_atomic;
+use crate::metrics::{Direction, Metrics};
+use crate::ratelimit::TokenBucket;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+// 流量统计写回磁盘的间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+// 检查日期变化的间隔，日流量与月流量最多在日期变化后这么久重新计数
+const PERIOD_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+// 客户端限速器超过该数量时清理已没有连接的客户端
+const MAX_IDLE_LIMITERS: usize = 1024;
+
+// 一个用户或客户端IP的流量，日流量与月流量在日期变化后重新计数
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Usage {
+    pub day: String,             // 日流量所属的日期，例如 2025-02-25
+    pub daily: u64,
+    pub month: String,           // 月流量所属的月份，例如 2025-02
+    pub monthly: u64,
+    pub total: u64,              // 累计流量，不会重置
+}
+
+impl Usage {
+    fn roll(&mut self, day: &str, month: &str) {
+        if self.day != day {
+            self.day = day.to_string();
+            self.daily = 0;
+        }
+        if self.month != month {
+            self.month = month.to_string();
+            self.monthly = 0;
+        }
+    }
+}
+
+// 流量配额(字节)，0表示不限制
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Quota {
+    pub daily: u64,
+    pub monthly: u64,
+}
+
+impl Quota {
+    // 配置文件中的配额以MB为单位
+    pub fn from_mb(daily: u64, monthly: u64) -> Self {
+        Quota {
+            daily: daily.saturating_mul(1024 * 1024),
+            monthly: monthly.saturating_mul(1024 * 1024),
+        }
+    }
+
+    fn exceeded(&self, daily: u64, monthly: u64) -> Option<&'static str> {
+        if self.daily > 0 && daily >= self.daily {
+            Some("今日")
+        } else if self.monthly > 0 && monthly >= self.monthly {
+            Some("本月")
+        } else {
+            None
+        }
+    }
+}
+
+// 计量的对象
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Account {
+    User(String),
+    Client(IpAddr),
+}
+
+impl std::fmt::Display for Account {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Account::User(name) => write!(f, "用户{}", name),
+            Account::Client(ip) => write!(f, "客户端{}", ip),
+        }
+    }
+}
+
+// 流量统计文件的内容，也用于显示当前的流量统计
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct UsageFile {
+    pub users: BTreeMap<String, Usage>,
+    pub clients: BTreeMap<String, Usage>, // 以客户端IP为键
+}
+
+// 日流量与月流量当前所属的日期与月份
+#[derive(Debug, Clone, PartialEq)]
+struct Period {
+    day: String,
+    month: String,
+}
+
+impl Period {
+    fn now() -> Self {
+        let now = chrono::Local::now();
+        Period {
+            day: now.format("%Y-%m-%d").to_string(),
+            month: now.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+// 有连接在计量的用户或客户端IP的流量计数，转发数据时只做原子加法
+// daily与monthly为当前周期的流量(含尚未合并的部分)，用于检查配额；pending为尚未合并到流量统计中的字节数
+#[derive(Default)]
+struct Counter {
+    daily: AtomicU64,
+    monthly: AtomicU64,
+    pending: AtomicU64,
+}
+
+impl Counter {
+    fn load(&self, usage: &Usage) {
+        self.daily.store(usage.daily, Ordering::Relaxed);
+        self.monthly.store(usage.monthly, Ordering::Relaxed);
+    }
+}
+
+// 连接计量的一个对象及其配额与计数
+struct Tracked {
+    account: Account,
+    quota: Quota,
+    counter: Arc<Counter>,
+}
+
+// 按用户与客户端IP统计的流量，定期写回磁盘，重启后继续累计
+// 锁的顺序: counters -> file
+pub struct UsageStore {
+    path: Option<PathBuf>,
+    file: Mutex<UsageFile>,
+    counters: Mutex<HashMap<Account, Arc<Counter>>>,
+    period: RwLock<Period>,
+    dirty: AtomicBool,
+}
+
+fn usage_of<'a>(file: &'a UsageFile, account: &Account) -> Option<&'a Usage> {
+    match account {
+        Account::User(name) => file.users.get(name),
+        Account::Client(ip) => file.clients.get(&ip.to_string()),
+    }
+}
+
+// 当前周期的流量，没有记录时为0，不会在流量统计中插入记录
+fn current_usage(file: &UsageFile, account: &Account, period: &Period) -> Usage {
+    let mut usage = usage_of(file, account).cloned().unwrap_or_default();
+    usage.roll(&period.day, &period.month);
+    usage
+}
+
+impl UsageStore {
+    fn new(path: Option<PathBuf>, file: UsageFile) -> Self {
+        UsageStore {
+            path,
+            file: Mutex::new(file),
+            counters: Mutex::new(HashMap::new()),
+            period: RwLock::new(Period::now()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    // 不落盘的流量统计
+    pub fn in_memory() -> Self {
+        Self::new(None, UsageFile::default())
+    }
+
+    // 从文件加载流量统计，文件不存在时从零开始
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = UsageFile::default();
+        if path.exists() {
+            let content = fs::read_to_string(&path)?;
+            if !content.trim().is_empty() {
+                file = serde_json::from_str(&content)
+                    .map_err(|e| anyhow::anyhow!("解析流量统计文件失败: {}", e))?;
+            }
+        }
+        Ok(Self::new(Some(path), file))
+    }
+
+    fn period(&self) -> Period {
+        self.period.read().unwrap().clone()
+    }
+
+    // 检查配额，任一对象的配额已用完时返回错误
+    pub fn check(&self, accounts: &[(Account, Quota)]) -> anyhow::Result<()> {
+        let period = self.period();
+        let counters = self.counters.lock().unwrap();
+        for (account, quota) in accounts {
+            let (daily, monthly) = match counters.get(account) {
+                Some(counter) => (counter.daily.load(Ordering::Relaxed), counter.monthly.load(Ordering::Relaxed)),
+                None => {
+                    let usage = current_usage(&self.file.lock().unwrap(), account, &period);
+                    (usage.daily, usage.monthly)
+                }
+            };
+            if let Some(period) = quota.exceeded(daily, monthly) {
+                return Err(anyhow::anyhow!("{}{}的流量配额已用完", account, period));
+            }
+        }
+        Ok(())
+    }
+
+    // 对象的流量计数，同一对象的所有连接共享，没有连接使用后在保存时清理
+    fn counter(&self, account: &Account) -> Arc<Counter> {
+        let mut counters = self.counters.lock().unwrap();
+        let counter = counters.entry(account.clone()).or_insert_with(|| {
+            let counter = Counter::default();
+            counter.load(&current_usage(&self.file.lock().unwrap(), account, &self.period()));
+            Arc::new(counter)
+        });
+        Arc::clone(counter)
+    }
+
+    // 记录流量，记录后超出配额时返回错误
+    fn record(&self, tracked: &[Tracked], bytes: u64) -> io::Result<()> {
+        let mut exceeded = None;
+        for Tracked { account, quota, counter } in tracked {
+            counter.pending.fetch_add(bytes, Ordering::Relaxed);
+            let daily = counter.daily.fetch_add(bytes, Ordering::Relaxed) + bytes;
+            let monthly = counter.monthly.fetch_add(bytes, Ordering::Relaxed) + bytes;
+            if exceeded.is_none() {
+                exceeded = quota.exceeded(daily, monthly).map(|period| format!("{}{}的流量配额已用完", account, period));
+            }
+        }
+        match exceeded {
+            Some(message) => Err(io::Error::other(message)),
+            None => Ok(()),
+        }
+    }
+
+    // 把各计数中尚未合并的流量按period计入流量统计，reload时再按流量统计重置计数的日流量与月流量
+    fn fold(&self, period: &Period, reload: bool) {
+        let counters: Vec<(Account, Arc<Counter>)> = self.counters.lock().unwrap()
+            .iter()
+            .map(|(account, counter)| (account.clone(), Arc::clone(counter)))
+            .collect();
+        let mut file = self.file.lock().unwrap();
+        for (account, counter) in counters {
+            let pending = counter.pending.swap(0, Ordering::Relaxed);
+            if pending > 0 {
+                let usage = match &account {
+                    Account::User(name) => file.users.entry(name.clone()).or_default(),
+                    Account::Client(ip) => file.clients.entry(ip.to_string()).or_default(),
+                };
+                usage.roll(&period.day, &period.month);
+                usage.daily += pending;
+                usage.monthly += pending;
+                usage.total += pending;
+                self.dirty.store(true, Ordering::Relaxed);
+            }
+            if reload {
+                counter.load(&current_usage(&file, &account, period));
+            }
+        }
+    }
+
+    // 日期变化后先把之前的流量计入旧的日期，再重置各计数的日流量与月流量
+    fn refresh_period(&self) {
+        let now = Period::now();
+        let previous = {
+            let mut period = self.period.write().unwrap();
+            if *period == now {
+                return;
+            }
+            std::mem::replace(&mut *period, now.clone())
+        };
+        self.fold(&previous, false);
+        self.fold(&now, true);
+    }
+
+    // 当前的流量统计，日流量与月流量已按当前日期重置
+    pub fn snapshot(&self) -> UsageFile {
+        let period = self.period();
+        self.fold(&period, false);
+        let mut snapshot = self.file.lock().unwrap().clone();
+        for usage in snapshot.users.values_mut().chain(snapshot.clients.values_mut()) {
+            usage.roll(&period.day, &period.month);
+        }
+        snapshot
+    }
+
+    // 合并各计数的流量，清理已没有连接的计数与没有流量的记录，有新流量时原子写回磁盘
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.fold(&self.period(), false);
+        self.counters.lock().unwrap()
+            .retain(|_, counter| Arc::strong_count(counter) > 1 || counter.pending.load(Ordering::Relaxed) > 0);
+        {
+            let mut file = self.file.lock().unwrap();
+            file.users.retain(|_, usage| usage.total > 0);
+            file.clients.retain(|_, usage| usage.total > 0);
+        }
+
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let content = {
+            let file = self.file.lock().unwrap();
+            serde_json::to_string_pretty(&*file)?
+        };
+        if let Err(e) = write_atomic(path, content.as_bytes()) {
+            self.dirty.store(true, Ordering::Relaxed);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    // 定期检查日期变化，并定期合并流量、写回磁盘
+    pub fn start_flush(self: &Arc<Self>) {
+        let store = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PERIOD_REFRESH_INTERVAL);
+            let mut last_flush = Instant::now();
+            loop {
+                interval.tick().await;
+                store.refresh_period();
+                if last_flush.elapsed() < FLUSH_INTERVAL {
+                    continue;
+                }
+                last_flush = Instant::now();
+                if let Err(e) = store.save() {
+                    eprintln!("保存流量统计失败: {}", e);
+                }
+            }
+        });
+    }
+}
+
+// 每个客户端IP所有连接共享的限速器
+#[derive(Default)]
+pub struct ClientLimiters {
+    buckets: Mutex<HashMap<IpAddr, Arc<TokenBucket>>>,
+}
+
+impl ClientLimiters {
+    // rate为0时不限速，速率变化后使用新的限速器
+    pub fn get(&self, ip: IpAddr, rate: u64) -> Option<Arc<TokenBucket>> {
+        if rate == 0 {
+            return None;
+        }
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_IDLE_LIMITERS {
+            buckets.retain(|_, bucket| Arc::strong_count(bucket) > 1);
+        }
+        let bucket = buckets.entry(ip)
+            .and_modify(|bucket| if bucket.rate() != rate {
+                *bucket = Arc::new(TokenBucket::new(rate));
+            })
+            .or_insert_with(|| Arc::new(TokenBucket::new(rate)));
+        Some(Arc::clone(bucket))
+    }
+}
+
+// 一个连接的计量器：按限速器限速，并把流量计入用户与客户端IP
+pub struct Meter {
+    usage: Arc<UsageStore>,
+    metrics: Arc<Metrics>,
+    tracked: Vec<Tracked>,
+    limiters: Vec<Arc<TokenBucket>>,
+}
+
+impl Meter {
+    pub fn new(usage: Arc<UsageStore>, metrics: Arc<Metrics>, accounts: Vec<(Account, Quota)>, limiters: Vec<Arc<TokenBucket>>) -> Self {
+        let tracked = accounts.into_iter()
+            .map(|(account, quota)| {
+                let counter = usage.counter(&account);
+                Tracked { account, quota, counter }
+            })
+            .collect();
+        Meter { usage, metrics, tracked, limiters }
+    }
+
+    // 在转发bytes字节之前调用，超出配额时返回错误以关闭连接
+    pub async fn consume(&self, bytes: usize, direction: Direction) -> io::Result<()> {
+        for limiter in &self.limiters {
+            limiter.acquire(bytes).await;
+        }
+        self.metrics.record_bytes(direction, bytes as u64);
+        self.usage.record(&self.tracked, bytes as u64)
+    }
+}
+
+// 显示各用户与客户端IP的流量
+pub fn print_usage(store: &UsageStore) {
+    let snapshot = store.snapshot();
+    for (title, entries) in [("用户流量:", snapshot.users), ("客户端流量:", snapshot.clients)] {
+        println!("\n{}", title.cyan().bold());
+        if entries.is_empty() {
+            println!("  {}", "暂无记录".yellow());
+        }
+        for (name, usage) in entries {
+            println!("  {:<16} {} {} {} {} {} {}",
+                name.cyan(),
+                "今日".green(),
+                format_bytes(usage.daily).yellow().bold(),
+                "本月".green(),
+                format_bytes(usage.monthly).yellow().bold(),
+                "累计".green(),
+                format_bytes(usage.total).yellow().bold()
+            );
+        }
+    }
+    println!();
+}
+
+// 以KB、MB、GB显示字节数
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn client(last: u8) -> Account {
+        Account::Client(IpAddr::from([10, 0, 0, last]))
+    }
+
+    fn meter(store: &Arc<UsageStore>, accounts: Vec<(Account, Quota)>) -> Meter {
+        Meter::new(Arc::clone(store), Arc::new(Metrics::default()), accounts, Vec::new())
+    }
+
+    #[tokio::test]
+    async fn enforces_quota_across_connections() {
+        let store = Arc::new(UsageStore::in_memory());
+        let accounts = vec![(client(1), Quota::from_mb(2, 0)), (Account::User("alice".into()), Quota::default())];
+        let first = meter(&store, accounts.clone());
+        let second = meter(&store, accounts.clone());
+
+        first.consume(MB as usize, Direction::Upload).await.unwrap();
+        assert!(store.check(&accounts).is_ok());
+        let error = second.consume(MB as usize, Direction::Download).await.unwrap_err();
+        assert!(error.to_string().contains("今日"), "{}", error);
+        assert!(store.check(&accounts).is_err());
+
+        let snapshot = store.snapshot();
+        assert_eq!(snapshot.clients["10.0.0.1"].daily, 2 * MB);
+        assert_eq!(snapshot.users["alice"].total, 2 * MB);
+    }
+
+    #[test]
+    fn check_does_not_create_records() {
+        let store = UsageStore::in_memory();
+        for last in 0..100 {
+            store.check(&[(client(last), Quota::from_mb(1, 1))]).unwrap();
+        }
+        assert!(store.snapshot().clients.is_empty());
+        assert!(store.counters.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_drops_idle_counters_and_empty_records() {
+        let store = Arc::new(UsageStore::in_memory());
+        let active = meter(&store, vec![(client(1), Quota::default())]);
+        drop(meter(&store, vec![(client(2), Quota::default())]));
+        active.consume(10, Direction::Upload).await.unwrap();
+        store.file.lock().unwrap().clients.insert("10.0.0.3".to_string(), Usage::default());
+
+        store.save().unwrap();
+        assert_eq!(store.counters.lock().unwrap().keys().collect::<Vec<_>>(), [&client(1)]);
+        assert_eq!(store.snapshot().clients.keys().collect::<Vec<_>>(), ["10.0.0.1"]);
+
+        // 重新建立连接后从流量统计恢复计数
+        drop(active);
+        store.save().unwrap();
+        assert!(store.counters.lock().unwrap().is_empty());
+        let again = meter(&store, vec![(client(1), Quota { daily: 15, monthly: 0 })]);
+        assert!(again.consume(5, Direction::Upload).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn day_change_resets_daily_usage() {
+        let store = Arc::new(UsageStore::in_memory());
+        *store.period.write().unwrap() = Period { day: "2000-01-31".to_string(), month: "2000-01".to_string() };
+        let meter = meter(&store, vec![(client(1), Quota { daily: 100, monthly: 0 })]);
+        assert!(meter.consume(100, Direction::Upload).await.is_err());
+
+        store.refresh_period();
+        meter.consume(50, Direction::Upload).await.unwrap();
+        let usage = &store.snapshot().clients["10.0.0.1"];
+        assert_eq!((usage.daily, usage.monthly, usage.total), (50, 50, 150));
+    }
+}
@@ -0,0 +1,503 @@
+use crate::config::{issues_error, ConfigIssue};
+use crate::proxy_pool::ProxyEntry;
+use crate::ratelimit::TokenBucket;
+use crate::usage::Quota;
+use anyhow::Result;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use colored::*;
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::SystemTime;
+use tokio::sync::Semaphore;
+
+// 用户文件中的单个用户，对应[[users]]
+// 拼错的字段可能让限制静默失效，因此不允许未知字段
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct UserConfig {
+    pub username: String,
+    pub password: String,        // argon2或bcrypt哈希，可用 lokipool hash-password 生成
+    pub groups: Vec<String>,     // 允许使用的代理分组(代理标签)，为空时不限制
+    pub max_connections: usize,  // 最大并发连接数，0表示不限制
+    pub bandwidth_limit: u64,    // 所有连接合计的带宽上限(KB/s)，0表示不限制
+    pub daily_quota: u64,        // 每天的流量配额(MB)，0表示不限制
+    pub monthly_quota: u64,      // 每月的流量配额(MB)，0表示不限制
+    pub allow: Vec<String>,      // 允许访问的目标，为空时不限制
+    pub deny: Vec<String>,       // 禁止访问的目标，优先于allow
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct UsersFile {
+    users: Vec<UserConfig>,
+}
+
+#[derive(Debug, Clone)]
+enum HostPattern {
+    Any,
+    Exact(String),
+    Suffix(String),
+    Net(IpNet),
+}
+
+// 目标地址规则:
+//   example.com       只匹配该域名
+//   *.example.com     匹配example.com及其子域名
+//   10.0.0.0/8        匹配该网段中的IP地址，只对IP形式的目标生效，不会解析域名
+//   *                 匹配所有目标
+// 规则后可以加端口，例如 *.example.com:443、*:80，IPv6地址或网段带端口时需要用方括号，例如 [2001:db8::/32]:443
+#[derive(Debug, Clone)]
+pub struct DestinationRule {
+    host: HostPattern,
+    port: Option<u16>,
+}
+
+impl DestinationRule {
+    pub fn parse(rule: &str) -> Result<Self> {
+        let rule = rule.trim();
+        let (host, port) = if let Some(rest) = rule.strip_prefix('[') {
+            let (host, after) = rest.split_once(']')
+                .ok_or_else(|| anyhow::anyhow!("缺少结束的]: {}", rule))?;
+            match after {
+                "" => (host, None),
+                _ => (host, Some(after.strip_prefix(':').ok_or_else(|| anyhow::anyhow!("无法解析的规则: {}", rule))?)),
+            }
+        } else if rule.matches(':').count() > 1 {
+            (rule, None)
+        } else {
+            match rule.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (rule, None),
+            }
+        };
+
+        let port = match port {
+            None | Some("*") => None,
+            Some(port) => Some(port.parse::<u16>().map_err(|_| anyhow::anyhow!("端口无效: {}", port))?),
+        };
+        let host = if host.is_empty() {
+            return Err(anyhow::anyhow!("规则缺少主机: {}", rule));
+        } else if host == "*" {
+            HostPattern::Any
+        } else if host.contains('/') {
+            HostPattern::Net(host.parse().map_err(|_| anyhow::anyhow!("网段无效: {}", host))?)
+        } else if let Ok(ip) = host.parse::<IpAddr>() {
+            HostPattern::Net(IpNet::from(ip))
+        } else if let Some(suffix) = host.strip_prefix("*.") {
+            HostPattern::Suffix(suffix.to_ascii_lowercase())
+        } else {
+            HostPattern::Exact(host.to_ascii_lowercase())
+        };
+        Ok(DestinationRule { host, port })
+    }
+
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        if self.port.is_some_and(|p| p != port) {
+            return false;
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        match &self.host {
+            HostPattern::Any => true,
+            HostPattern::Exact(exact) => host == *exact,
+            HostPattern::Suffix(suffix) => host == *suffix || host.ends_with(&format!(".{}", suffix)),
+            HostPattern::Net(net) => host.trim_start_matches('[').trim_end_matches(']')
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| net.contains(&ip.to_canonical())),
+        }
+    }
+}
+
+// 加载后的用户
+pub struct User {
+    pub username: String,
+    password_hash: String,
+    pub groups: Vec<String>,
+    pub max_connections: usize,
+    allow: Vec<DestinationRule>,
+    deny: Vec<DestinationRule>,
+    limiter: Option<Arc<TokenBucket>>,
+    pub quota: Quota,
+}
+
+impl User {
+    pub fn verify(&self, password: &str) -> bool {
+        verify_password(&self.password_hash, password)
+    }
+
+    // 是否允许使用该代理，代理需要含有用户的任一分组标签
+    pub fn allows_proxy(&self, proxy: &ProxyEntry) -> bool {
+        self.groups.is_empty() || proxy.tags.iter().any(|tag| self.groups.contains(tag))
+    }
+
+    pub fn allows_destination(&self, host: &str, port: u16) -> bool {
+        !self.deny.iter().any(|rule| rule.matches(host, port))
+            && (self.allow.is_empty() || self.allow.iter().any(|rule| rule.matches(host, port)))
+    }
+
+    // 该用户所有连接共享的限速器
+    pub fn limiter(&self) -> Option<&Arc<TokenBucket>> {
+        self.limiter.as_ref()
+    }
+}
+
+fn verify_password(password_hash: &str, password: &str) -> bool {
+    if password_hash.starts_with("$argon2") {
+        PasswordHash::new(password_hash)
+            .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+    } else {
+        bcrypt::verify(password, password_hash).unwrap_or(false)
+    }
+}
+
+// 用户不存在时校验的哈希，使认证耗时与用户名是否存在无关
+fn dummy_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash_password("lokipool-unknown-user").unwrap_or_default())
+}
+
+fn check_hash(hash: &str) -> Result<(), &'static str> {
+    if hash.starts_with("$argon2") {
+        return PasswordHash::new(hash).map(|_| ()).map_err(|_| "argon2哈希格式无效");
+    }
+    let bcrypt_prefix = ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix));
+    if bcrypt_prefix && hash.len() == 60 {
+        Ok(())
+    } else {
+        Err("必须是argon2或bcrypt哈希，可用 lokipool hash-password 生成")
+    }
+}
+
+// 生成argon2id密码哈希
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow::anyhow!("生成密码哈希失败: {}", e))
+}
+
+// 解析并检查用户文件，一次列出所有问题
+// previous中带宽上限未变化的用户沿用原有的限速器，避免重载时清空限速状态
+fn build_users(content: &str, previous: &HashMap<String, Arc<User>>) -> Result<HashMap<String, Arc<User>>> {
+    let file: UsersFile = toml::from_str(content)
+        .map_err(|e| anyhow::anyhow!("解析用户文件失败: {}", e))?;
+
+    let mut issues = Vec::new();
+    let mut users = HashMap::new();
+    for (i, config) in file.users.into_iter().enumerate() {
+        let key = format!("users[{}]", i);
+        let mut issue = |field: &str, message: String| issues.push(ConfigIssue {
+            key: format!("{}.{}", key, field),
+            message,
+        });
+
+        if config.username.is_empty() {
+            issue("username", "用户名不能为空".to_string());
+        } else if config.username.len() > 255 {
+            issue("username", "长度不能超过255字节".to_string());
+        } else if users.contains_key(&config.username) {
+            issue("username", format!("用户名重复: {}", config.username));
+        }
+        if let Err(message) = check_hash(&config.password) {
+            issue("password", message.to_string());
+        }
+        let mut parse_rules = |field: &str, rules: &[String]| -> Vec<DestinationRule> {
+            rules.iter().enumerate()
+                .filter_map(|(j, rule)| match DestinationRule::parse(rule) {
+                    Ok(rule) => Some(rule),
+                    Err(e) => {
+                        issue(&format!("{}[{}]", field, j), e.to_string());
+                        None
+                    }
+                })
+                .collect()
+        };
+        let allow = parse_rules("allow", &config.allow);
+        let deny = parse_rules("deny", &config.deny);
+
+        let rate = config.bandwidth_limit.saturating_mul(1024);
+        let limiter = (rate > 0).then(|| match previous.get(&config.username).and_then(|user| user.limiter.as_ref()) {
+            Some(limiter) if limiter.rate() == rate => Arc::clone(limiter),
+            _ => Arc::new(TokenBucket::new(rate)),
+        });
+        users.insert(config.username.clone(), Arc::new(User {
+            username: config.username,
+            password_hash: config.password,
+            groups: config.groups,
+            max_connections: config.max_connections,
+            allow,
+            deny,
+            limiter,
+            quota: Quota::from_mb(config.daily_quota, config.monthly_quota),
+        }));
+    }
+
+    if !issues.is_empty() {
+        return Err(issues_error("用户文件", &issues));
+    }
+    Ok(users)
+}
+
+// 检查用户文件，返回用户数
+pub fn check_file(path: &str) -> Result<usize> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("读取用户文件失败: {} - {}", path, e))?;
+    Ok(build_users(&content, &HashMap::new())?.len())
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[derive(Default)]
+struct UserState {
+    path: String,
+    modified: Option<SystemTime>,
+    users: HashMap<String, Arc<User>>,
+}
+
+// 用户表，认证时检查用户文件的路径与修改时间，变化后自动重新加载
+pub struct UserStore {
+    state: RwLock<UserState>,
+    connections: Mutex<HashMap<String, usize>>, // 各用户当前的连接数
+    verifying: Semaphore,                       // 限制同时进行的密码校验数，避免认证请求占满阻塞线程池
+}
+
+impl Default for UserStore {
+    fn default() -> Self {
+        UserStore {
+            state: RwLock::default(),
+            connections: Mutex::default(),
+            verifying: Semaphore::new(std::thread::available_parallelism().map_or(4, |n| n.get())),
+        }
+    }
+}
+
+impl UserStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 加载用户文件，失败时保留原有用户并返回错误
+    pub fn load(&self, path: &str) -> Result<usize> {
+        let modified = modified_time(path);
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("读取用户文件失败: {} - {}", path, e))?;
+        let users = build_users(&content, &self.state.read().unwrap().users)?;
+        let count = users.len();
+        *self.state.write().unwrap() = UserState {
+            path: path.to_string(),
+            modified,
+            users,
+        };
+        Ok(count)
+    }
+
+    // 用户文件路径或修改时间变化时重新加载，失败时输出错误并继续使用原有用户
+    fn refresh(&self, path: &str) {
+        let modified = modified_time(path);
+        {
+            let state = self.state.read().unwrap();
+            if state.path == path && state.modified == modified {
+                return;
+            }
+        }
+        if path.is_empty() {
+            *self.state.write().unwrap() = UserState::default();
+            return;
+        }
+        match self.load(path) {
+            Ok(count) => println!("{} {}", "已重载用户文件，用户数:".green().bold(), count.to_string().yellow()),
+            Err(e) => {
+                eprintln!("{} {}", "重载用户文件失败，继续使用原有用户:".red().bold(), e);
+                // 记录失败的文件状态，文件再次修改前不重复加载
+                let mut state = self.state.write().unwrap();
+                state.path = path.to_string();
+                state.modified = modified;
+            }
+        }
+    }
+
+    // 验证用户名与密码，用户文件检查与密码哈希的计算在阻塞线程池中进行
+    // 用户不存在时同样校验一次哈希，避免通过响应时间判断用户名是否存在
+    pub async fn authenticate(self: &Arc<Self>, path: &str, username: &str, password: &str) -> Option<Arc<User>> {
+        let _permit = self.verifying.acquire().await.ok()?;
+        let store = Arc::clone(self);
+        let (path, username, password) = (path.to_string(), username.to_string(), password.to_string());
+        tokio::task::spawn_blocking(move || {
+            store.refresh(&path);
+            let user = store.state.read().unwrap().users.get(&username).cloned();
+            match user {
+                Some(user) => user.verify(&password).then_some(user),
+                None => {
+                    verify_password(dummy_hash(), &password);
+                    None
+                }
+            }
+        }).await.ok().flatten()
+    }
+
+    // 占用用户的一个连接名额，达到最大连接数时返回None，名额在返回值被丢弃时释放
+    pub fn acquire(self: &Arc<Self>, user: &User) -> Option<UserGuard> {
+        let mut connections = self.connections.lock().unwrap();
+        let count = connections.entry(user.username.clone()).or_insert(0);
+        if user.max_connections > 0 && *count >= user.max_connections {
+            return None;
+        }
+        *count += 1;
+        Some(UserGuard {
+            store: Arc::clone(self),
+            username: user.username.clone(),
+        })
+    }
+}
+
+pub struct UserGuard {
+    store: Arc<UserStore>,
+    username: String,
+}
+
+impl Drop for UserGuard {
+    fn drop(&mut self) {
+        let mut connections = self.store.connections.lock().unwrap();
+        if let Some(count) = connections.get_mut(&self.username) {
+            *count -= 1;
+            if *count == 0 {
+                connections.remove(&self.username);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_destination_rules() {
+        let valid = ["*", "*:25", "example.com", "*.example.com:443", "10.0.0.0/8", "10.0.0.1:80",
+            "2001:db8::1", "2001:db8::/32", "[2001:db8::1]", "[2001:db8::1]:443", "[::1]:*"];
+        for rule in valid {
+            assert!(DestinationRule::parse(rule).is_ok(), "{}", rule);
+        }
+        let invalid = ["", ":80", "example.com:http", "example.com:70000", "10.0.0.0/33", "[::1", "[::1]80", "[]:80"];
+        for rule in invalid {
+            assert!(DestinationRule::parse(rule).is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn matches_destinations() {
+        let cases = [
+            ("*", "anything.test", 1, true),
+            ("*:25", "mail.test", 25, true),
+            ("*:25", "mail.test", 26, false),
+            ("Example.COM", "example.com.", 80, true),
+            ("example.com", "www.example.com", 80, false),
+            ("*.example.com", "example.com", 80, true),
+            ("*.example.com", "a.b.example.com", 80, true),
+            ("*.example.com", "badexample.com", 80, false),
+            ("10.0.0.0/8", "10.255.0.1", 80, true),
+            ("10.0.0.0/8", "11.0.0.1", 80, false),
+            ("10.0.0.0/8", "::ffff:10.0.0.1", 80, true),
+            ("10.0.0.0/8", "ten.example", 80, false),
+            ("2001:db8::/32", "2001:db8::1", 80, true),
+            ("2001:db8::/32", "[2001:db8::1]", 80, true),
+            ("2001:db8::/32", "2001:db9::1", 80, false),
+            ("[::1]:443", "::1", 443, true),
+            ("[::1]:443", "::1", 80, false),
+        ];
+        for (rule, host, port, expected) in cases {
+            let parsed = DestinationRule::parse(rule).unwrap();
+            assert_eq!(parsed.matches(host, port), expected, "{} {}:{}", rule, host, port);
+        }
+    }
+
+    #[test]
+    fn huge_bandwidth_limit_saturates() {
+        let content = format!(r#"
+            [[users]]
+            username = "alice"
+            password = "{}"
+            bandwidth_limit = {}
+        "#, hash_password("secret").unwrap(), i64::MAX);
+        let users = build_users(&content, &HashMap::new()).unwrap();
+        assert!(users["alice"].limiter().unwrap().rate() > i64::MAX as u64);
+    }
+
+    #[test]
+    fn user_deny_overrides_overlapping_allow() {
+        let content = format!(r#"
+            [[users]]
+            username = "alice"
+            password = "{}"
+            allow = ["10.0.0.0/8", "*.example.com", "2001:db8::/32"]
+            deny = ["10.1.0.0/16", "admin.example.com", "*:25"]
+        "#, hash_password("secret").unwrap());
+        let users = build_users(&content, &HashMap::new()).unwrap();
+        let user = &users["alice"];
+        assert!(user.verify("secret") && !user.verify("Secret") && !user.verify(""));
+
+        let cases = [
+            ("10.0.0.1", 80, true),
+            ("10.1.0.1", 80, false),
+            ("10.0.0.1", 25, false),
+            ("www.example.com", 443, true),
+            ("admin.example.com", 443, false),
+            ("2001:db8::1", 443, true),
+            ("2001:db9::1", 443, false),
+            ("8.8.8.8", 53, false),
+        ];
+        for (host, port, expected) in cases {
+            assert_eq!(user.allows_destination(host, port), expected, "{}:{}", host, port);
+        }
+    }
+
+    #[test]
+    fn reports_invalid_users() {
+        let content = r#"
+            [[users]]
+            username = ""
+            password = "plain"
+            deny = ["10.0.0.0/33"]
+        "#;
+        let error = build_users(content, &HashMap::new()).err().unwrap().to_string();
+        for key in ["users[0].username", "users[0].password", "users[0].deny[0]"] {
+            assert!(error.contains(key), "{}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn authenticates_and_reloads_changed_file() {
+        let dir = std::env::temp_dir().join(format!("lokipool-users-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("users.toml");
+        let write = |username: &str| std::fs::write(&path, format!(
+            "[[users]]\nusername = \"{}\"\npassword = \"{}\"\n", username, hash_password("secret").unwrap()
+        )).unwrap();
+        write("alice");
+        let path_str = path.display().to_string();
+
+        let store = Arc::new(UserStore::new());
+        assert!(store.authenticate(&path_str, "alice", "secret").await.is_some());
+        assert!(store.authenticate(&path_str, "alice", "wrong").await.is_none());
+        // 不存在的用户校验的是占位哈希，同样失败
+        assert!(dummy_hash().starts_with("$argon2"));
+        assert!(store.authenticate(&path_str, "bob", "secret").await.is_none());
+
+        // 修改时间变化后重新加载
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        write("bob");
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(1)).unwrap();
+        assert!(store.authenticate(&path_str, "bob", "secret").await.is_some());
+        assert!(store.authenticate(&path_str, "alice", "secret").await.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}